@@ -1,7 +1,15 @@
 #![allow(clippy::missing_errors_doc)]
 
 use anyhow::{ Result, bail };
-use clap::{ Args, Parser, Subcommand };
+use catbox::{
+    album::AlbumFile,
+    id::{ AlbumShort, FileId, UserHash },
+    naming::{ RemoteName, Template },
+    upload::Compression,
+    url::{ album_short, file_id },
+};
+use clap::{ Args, Parser, Subcommand, ValueEnum };
+use std::{ ffi::OsString, fs, io::Read, path::Path };
 
 #[derive(Debug, PartialEq, Subcommand)]
 pub enum CatboxCommand {
@@ -9,6 +17,15 @@ pub enum CatboxCommand {
     Delete(Delete),
     Album(Album),
     Litter(Litter),
+    Download(Download),
+    Trash(Trash),
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum TrashCommand {
+    List(TrashList),
+    Restore(TrashRestore),
+    Purge(TrashPurge),
 }
 
 #[derive(Debug, PartialEq, Subcommand)]
@@ -18,8 +35,133 @@ pub enum AlbumCommand {
     Edit(AlbumEdit),
     Remove(AlbumRemove),
     Delete(AlbumDelete),
+    Show(AlbumShow),
+    Download(AlbumDownload),
+    Sync(AlbumSync),
+    Diff(AlbumDiff),
+    Clone(AlbumClone),
+    Merge(AlbumMerge),
+    Split(AlbumSplit),
+    Reorder(AlbumReorder),
+    Backup(AlbumBackup),
+    Restore(AlbumRestore),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SplitBy {
+    Extension,
+    Count,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    /// Name of the uploaded local file, or the ID
+    Name,
+    /// Upload date
+    Date,
+    Size,
+    /// Date the photo was taken
+    ExifDate,
+}
+
+/// Items read from a file or stdin in addition to the positional arguments
+#[derive(Debug, Default, PartialEq, Args)]
+pub struct ListInput {
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Also read items from a file, one per line, or from stdin with -"
+    )]
+    pub from_file: Option<String>,
+
+    #[arg(
+        short = '0',
+        long = "null",
+        requires = "from_file",
+        help = "Items are separated by NUL characters, e.g. from find -print0"
+    )]
+    pub null: bool,
+}
+
+impl ListInput {
+    /// Read and parse the items, skipping empty ones
+    ///
+    /// # Arguments
+    ///
+    /// * `parse` - Parser for a single item, the same as for the positional arguments
+    pub fn read<T>(&self, parse: fn(&str) -> Result<T>) -> Result<Vec<T>> {
+        let Some(from_file) = &self.from_file else {
+            return Ok(Vec::new());
+        };
+
+        let mut input = String::new();
+
+        if from_file == "-" {
+            std::io::stdin().read_to_string(&mut input)?;
+        } else {
+            input = fs::read_to_string(from_file)?;
+        }
+
+        let items: Vec<_> = if self.null {
+            input.split('\0').collect()
+        } else {
+            input.lines().map(str::trim).collect()
+        };
+
+        items
+            .into_iter()
+            .filter(|item| !item.is_empty())
+            .map(parse)
+            .collect()
+    }
 }
 
+/// Replace `@file` arguments with the lines of the file, one argument per line
+///
+/// A leading `@@` stands for a literal `@`.
+pub fn expand_argfiles<I: IntoIterator<Item = OsString>>(args: I) -> Result<Vec<OsString>> {
+    let mut expanded = Vec::new();
+
+    for arg in args {
+        let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix('@')) else {
+            expanded.push(arg);
+            continue;
+        };
+
+        if path.starts_with('@') {
+            expanded.push(path.into());
+            continue;
+        }
+
+        let Ok(contents) = fs::read_to_string(path) else {
+            bail!("Cannot read the argument file {path}");
+        };
+
+        expanded.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(OsString::from)
+        );
+    }
+
+    Ok(expanded)
+}
+
+/// User hash given with `--user` or `CATBOX_USER_HASH`
+///
+/// Not written as `Option<UserHash>`, so clap stores the parsed option as it is,
+/// and an empty hash can mean no hash.
+pub type OptionalUserHash = Option<UserHash>;
+
 #[derive(Debug, Parser)]
 #[command(about = "Unofficial Catbox.moe CLI", version)]
 pub struct CatboxArgs {
@@ -30,17 +172,63 @@ pub struct CatboxArgs {
         global = true,
         short,
         long = "user",
-        help = "Catbox user hash",
-        env = "CATBOX_USER_HASH"
+        help = "Catbox user hash, empty for anonymous requests",
+        env = "CATBOX_USER_HASH",
+        value_parser = optional_user_hash,
+        default_value = "",
+        hide_default_value = true
     )]
-    pub user_hash: Option<String>,
+    pub user_hash: OptionalUserHash,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Print the requests which would be sent instead of sending them, \
+            and leave the ledger and trash unchanged"
+    )]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Upload to Catbox (max. 200MB)", arg_required_else_help(true))]
 pub struct Upload {
     #[arg(from_global)]
-    pub user_hash: String,
+    pub user_hash: OptionalUserHash,
+
+    #[arg(short, long, help = "Encrypt files before uploading, the key is appended to the link")]
+    pub encrypt: bool,
+
+    #[arg(
+        long,
+        requires = "encrypt",
+        help = "Also upload an HTML page which decrypts the file in the browser"
+    )]
+    pub share_page: bool,
+
+    #[arg(short, long, help = "Compress files before uploading (Options: zstd, gzip)")]
+    pub compress: Option<Compression>,
+
+    #[arg(long, help = "Remove EXIF, GPS and XMP metadata from JPEG, PNG and WebP images")]
+    pub strip_metadata: bool,
+
+    #[arg(
+        long,
+        value_parser = valid_size,
+        help = "Re-encode images larger than this size, e.g. 10M (max. 200M)"
+    )]
+    pub max_size: Option<u64>,
+
+    #[arg(long, help = "Downscale images whose width or height exceeds this many pixels")]
+    pub max_dimension: Option<u32>,
+
+    #[arg(long, help = "Correct missing or wrong extensions of images, audio, video and PDFs")]
+    pub fix_extension: bool,
+
+    #[arg(long, help = "Content type to send instead of the detected one")]
+    pub mime: Option<String>,
+
+    #[command(flatten)]
+    pub naming: Naming,
 
     #[arg(num_args(1..), help = "File paths or URLs")]
     pub files: Vec<String>,
@@ -49,11 +237,17 @@ pub struct Upload {
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Delete files", arg_required_else_help(true))]
 pub struct Delete {
+    #[arg(long, help = "Keep local copies of the files in the trash, see catbox trash")]
+    pub trash: bool,
+
     #[arg(from_global)]
-    pub user_hash: String,
+    pub user_hash: OptionalUserHash,
 
-    #[arg(num_args(1..), help = "File IDs")]
-    pub files: Vec<String>,
+    #[command(flatten)]
+    pub input: ListInput,
+
+    #[arg(num_args(1..), value_parser = file_id, help = "File IDs or URLs")]
+    pub files: Vec<FileId>,
 }
 
 #[derive(Debug, PartialEq, Args)]
@@ -63,16 +257,293 @@ pub struct Album {
     pub album_command: AlbumCommand,
 }
 
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Local copies of deleted files", arg_required_else_help(true))]
+pub struct Trash {
+    #[command(subcommand)]
+    pub trash_command: TrashCommand,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "List the files in the trash")]
+pub struct TrashList {
+    #[arg(short, long, value_enum, default_value_t, help = "Output format")]
+    pub format: Format,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    about = "Upload files from the trash again and add them to their albums",
+    arg_required_else_help(true)
+)]
+pub struct TrashRestore {
+    #[arg(from_global)]
+    pub user_hash: OptionalUserHash,
+
+    #[arg(num_args(1..), value_parser = file_id, help = "IDs or URLs of the deleted files")]
+    pub files: Vec<FileId>,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Remove files from the trash for good", arg_required_else_help(true))]
+pub struct TrashPurge {
+    #[arg(long, conflicts_with = "files", help = "Remove all files")]
+    pub all: bool,
+
+    #[arg(
+        required_unless_present = "all",
+        value_parser = file_id,
+        help = "IDs or URLs of the deleted files"
+    )]
+    pub files: Vec<FileId>,
+}
+
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Upload a temporary file to Litterbox (max. 1GB)", arg_required_else_help(true))]
 pub struct Litter {
     #[arg(short, long, help = "File lifetime in hours", value_parser = valid_hour)]
     pub time: Option<u8>,
 
+    #[arg(long, help = "Remove EXIF, GPS and XMP metadata from JPEG, PNG and WebP images")]
+    pub strip_metadata: bool,
+
+    #[arg(long, help = "Correct missing or wrong extensions of images, audio, video and PDFs")]
+    pub fix_extension: bool,
+
+    #[arg(long, help = "Content type to send instead of the detected one")]
+    pub mime: Option<String>,
+
+    #[command(flatten)]
+    pub naming: Naming,
+
     #[arg(num_args(1..), help = "File paths")]
     pub files: Vec<String>,
 }
 
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Download files, decrypting encrypted uploads", arg_required_else_help(true))]
+pub struct Download {
+    #[arg(short, long, default_value = ".", help = "Output directory")]
+    pub output: String,
+
+    #[arg(short, long, help = "Decompress files uploaded with --compress")]
+    pub decompress: bool,
+
+    #[arg(long, help = "Replace existing files instead of skipping them")]
+    pub overwrite: bool,
+
+    #[arg(short, long, help = "Name to save the file as (only for a single file)")]
+    pub name: Option<String>,
+
+    #[arg(num_args(1..), help = "File URLs or IDs")]
+    pub urls: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Show the contents of an album", arg_required_else_help(true))]
+pub struct AlbumShow {
+    #[arg(short, long, value_enum, default_value_t, help = "Output format")]
+    pub format: Format,
+
+    #[arg(value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Download all files of an album", arg_required_else_help(true))]
+pub struct AlbumDownload {
+    #[arg(short, long, default_value = ".", help = "Output directory")]
+    pub output: String,
+
+    #[arg(long, help = "Save the files in a subfolder named after the album title")]
+    pub subfolder: bool,
+
+    #[arg(long, help = "Replace existing files instead of skipping them")]
+    pub overwrite: bool,
+
+    #[arg(value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Make an album mirror the files in a directory", arg_required_else_help(true))]
+pub struct AlbumSync {
+    #[arg(short = 'a', long = "album", value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
+
+    #[arg(long, help = "Also delete removed files uploaded with this tool and in no other album")]
+    pub delete_orphans: bool,
+
+    #[arg(from_global)]
+    pub user_hash: OptionalUserHash,
+
+    #[arg(help = "Directory with the files of the album")]
+    pub dir: String,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    about = "Compare an album with a directory or another album",
+    arg_required_else_help(true)
+)]
+pub struct AlbumDiff {
+    #[arg(short, long, value_enum, default_value_t, help = "Output format")]
+    pub format: Format,
+
+    #[arg(value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
+
+    #[arg(help = "Directory, or ID or URL of another album")]
+    pub other: String,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Create a copy of an album", arg_required_else_help(true))]
+pub struct AlbumClone {
+    #[arg(short, long, help = "Title of the copy [default: title of the album]")]
+    pub title: Option<String>,
+
+    #[arg(from_global)]
+    pub user_hash: OptionalUserHash,
+
+    #[arg(value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Create an album with the files of several albums", arg_required_else_help(true))]
+pub struct AlbumMerge {
+    #[arg(long, help = "Title of the new album")]
+    pub into: String,
+
+    #[arg(
+        short,
+        long,
+        alias = "desc",
+        help = "Description of the new album [default: description of the first album]"
+    )]
+    pub description: Option<String>,
+
+    #[arg(from_global)]
+    pub user_hash: OptionalUserHash,
+
+    #[arg(num_args(2..), value_parser = album_short, help = "Album IDs or URLs")]
+    pub shorts: Vec<AlbumShort>,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Divide the files of an album into new albums", arg_required_else_help(true))]
+pub struct AlbumSplit {
+    #[arg(long, value_enum, help = "How to divide the files")]
+    pub by: SplitBy,
+
+    #[arg(
+        long,
+        default_value_t = 50,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of files per album when dividing by count"
+    )]
+    pub size: u16,
+
+    #[arg(from_global)]
+    pub user_hash: OptionalUserHash,
+
+    #[arg(value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Change the order of the files in an album", arg_required_else_help(true))]
+pub struct AlbumReorder {
+    #[arg(
+        short,
+        long,
+        value_enum,
+        required_unless_present = "order_from",
+        help = "Sort the files, files without the information come last"
+    )]
+    pub sort: Option<SortBy>,
+
+    #[arg(
+        long,
+        conflicts_with = "sort",
+        help = "File with one ID or URL per line, unlisted files come last"
+    )]
+    pub order_from: Option<String>,
+
+    #[arg(short, long, help = "Reverse the order")]
+    pub reverse: bool,
+
+    #[arg(from_global)]
+    pub user_hash: OptionalUserHash,
+
+    #[arg(value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(about = "Save albums to local snapshots", arg_required_else_help(true))]
+pub struct AlbumBackup {
+    #[arg(short, long, default_value = ".", help = "Directory to save the snapshots in")]
+    pub output: String,
+
+    #[arg(long, help = "Also save the contents of the files, in a tar archive")]
+    pub with_files: bool,
+
+    #[arg(num_args(1..), value_parser = album_short, help = "Album IDs or URLs")]
+    pub shorts: Vec<AlbumShort>,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    about = "Recreate an album from a snapshot, re-uploading deleted files",
+    arg_required_else_help(true)
+)]
+pub struct AlbumRestore {
+    #[arg(
+        short = 'a',
+        long = "album",
+        value_parser = album_short,
+        help = "Restore into this album instead of creating a new one"
+    )]
+    pub short: Option<AlbumShort>,
+
+    #[arg(from_global)]
+    pub user_hash: OptionalUserHash,
+
+    #[arg(help = "Snapshot created by album backup")]
+    pub snapshot: String,
+}
+
+#[derive(Debug, PartialEq, Args)]
+#[group(multiple = false)]
+pub struct Naming {
+    #[arg(long, help = "Name to upload the file with")]
+    pub name: Option<String>,
+
+    #[arg(
+        long,
+        help = "Template for uploaded file names, e.g. '{hash8}.{ext}' \
+                (Placeholders: {name}, {stem}, {ext}, {hash}, {hash8}, {random})"
+    )]
+    pub name_template: Option<Template>,
+
+    #[arg(long, help = "Upload files with random names, keeping their extensions")]
+    pub anonymize_names: bool,
+}
+
+impl Naming {
+    pub fn remote_name(&self) -> Option<RemoteName> {
+        if let Some(name) = &self.name {
+            Some(RemoteName::Fixed(name.clone()))
+        } else if let Some(template) = &self.name_template {
+            Some(RemoteName::Template(template.clone()))
+        } else {
+            self.anonymize_names.then_some(RemoteName::Anonymous)
+        }
+    }
+}
+
 fn valid_hour(hour: &str) -> Result<u8> {
     if let Ok(hour) = hour.parse::<u8>() {
         if [1, 12, 24, 72].contains(&hour) {
@@ -85,6 +556,67 @@ fn valid_hour(hour: &str) -> Result<u8> {
     }
 }
 
+/// Parse a user hash, an empty hash means anonymous requests
+fn optional_user_hash(user_hash: &str) -> Result<Option<UserHash>> {
+    if user_hash.is_empty() { Ok(None) } else { Ok(Some(user_hash.parse()?)) }
+}
+
+/// Get the user hash of commands which cannot be run anonymously
+pub fn required(user_hash: OptionalUserHash) -> Result<UserHash> {
+    let Some(user_hash) = user_hash else {
+        bail!("This command requires a user hash, given with --user or CATBOX_USER_HASH");
+    };
+
+    Ok(user_hash)
+}
+
+/// Treat existing paths as local files, and everything else as files on Catbox
+///
+/// Inputs which are neither, but look like a path, are reported as missing files.
+pub fn album_file(file: &str) -> Result<AlbumFile> {
+    let path = Path::new(file);
+
+    if path.is_file() {
+        return Ok(AlbumFile::Local(file.to_string()));
+    }
+
+    match file_id(file) {
+        Ok(id) => Ok(AlbumFile::Remote(id)),
+        Err(_) if looks_like_path(path) => bail!("{file} was not found"),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether an input is meant as a local path, i.e. it is relative to a local directory
+/// or its directory exists
+fn looks_like_path(path: &Path) -> bool {
+    let file = path.to_string_lossy();
+
+    !file.contains("://") &&
+        (file.starts_with(['.', '/', '~']) ||
+            path.parent().is_some_and(|dir| !dir.as_os_str().is_empty() && dir.is_dir()))
+}
+
+fn valid_size(size: &str) -> Result<u64> {
+    let (number, unit) = size.split_at(
+        size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len())
+    );
+
+    let multiplier = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" => 1024,
+        "M" => 1024 * 1024,
+        "G" => 1024 * 1024 * 1024,
+        _ => bail!("{size} is not a valid size (e.g. 500K, 10M, 1G)"),
+    };
+
+    if let Ok(number) = number.parse::<u64>() && let Some(bytes) = number.checked_mul(multiplier) {
+        Ok(bytes)
+    } else {
+        bail!("{size} is not a valid size (e.g. 500K, 10M, 1G)");
+    }
+}
+
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Create an album", arg_required_else_help(true))]
 pub struct AlbumCreate {
@@ -95,63 +627,114 @@ pub struct AlbumCreate {
     pub description: Option<String>,
 
     #[arg(from_global)]
-    pub user_hash: String,
+    pub user_hash: OptionalUserHash,
 
-    #[arg(num_args(1..), help = "File IDs")]
-    pub files: Vec<String>,
+    #[command(flatten)]
+    pub input: ListInput,
+
+    #[arg(
+        num_args(1..),
+        value_parser = album_file,
+        help = "Local files to upload, or IDs or URLs of files on Catbox"
+    )]
+    pub files: Vec<AlbumFile>,
 }
 
 #[derive(Debug, PartialEq, Args)]
-#[command(about = "Edit an album", arg_required_else_help(true))]
+#[command(about = "Edit an album, keeping everything not given", arg_required_else_help(true))]
 pub struct AlbumEdit {
-    #[arg(short, long, help = "Album ID")]
-    pub short: String,
+    #[arg(short, long, value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
 
-    #[arg(short, long, help = "Album title")]
-    pub title: String,
+    #[arg(short, long, help = "New album title")]
+    pub title: Option<String>,
 
-    #[arg(short, long, alias = "desc", help = "Album description")]
+    #[arg(short, long, alias = "desc", help = "New album description")]
     pub description: Option<String>,
 
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = file_id,
+        help = "Catbox IDs of files to add to the album"
+    )]
+    pub add: Vec<FileId>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = file_id,
+        help = "Catbox IDs of files to remove from the album"
+    )]
+    pub remove: Vec<FileId>,
+
+    #[arg(long, help = "Only print the changes, without editing the album")]
+    pub preview: bool,
+
     #[arg(from_global)]
-    pub user_hash: String,
+    pub user_hash: OptionalUserHash,
 
-    #[arg(num_args(1..), help = "Album ID")]
-    pub files: Vec<String>,
+    #[arg(
+        num_args(1..),
+        value_parser = file_id,
+        conflicts_with_all = ["add", "remove"],
+        help = "Catbox IDs of all files the album should contain"
+    )]
+    pub files: Vec<FileId>,
 }
 
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Add files to an album", arg_required_else_help(true))]
 pub struct AlbumAdd {
-    #[arg(short, long, help = "Album ID")]
-    pub short: String,
+    #[arg(short, long, value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
 
     #[arg(from_global)]
-    pub user_hash: String,
+    pub user_hash: OptionalUserHash,
 
-    #[arg(num_args(1..), help = "Catbox IDs of the files to add to the album")]
-    pub files: Vec<String>,
+    #[command(flatten)]
+    pub input: ListInput,
+
+    #[arg(
+        num_args(1..),
+        value_parser = album_file,
+        help = "Local files, or IDs or URLs of files to add to the album"
+    )]
+    pub files: Vec<AlbumFile>,
 }
 
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Remove files from an album", arg_required_else_help(true))]
 pub struct AlbumRemove {
-    #[arg(short, long, help = "Album ID")]
-    pub short: String,
+    #[arg(short, long, value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
 
     #[arg(from_global)]
-    pub user_hash: String,
+    pub user_hash: OptionalUserHash,
 
-    #[arg(num_args(1..), help = "Catbox IDs of the files to remove from the album")]
-    pub files: Vec<String>,
+    #[command(flatten)]
+    pub input: ListInput,
+
+    #[arg(
+        num_args(1..),
+        value_parser = album_file,
+        help = "Local files, or IDs or URLs of files to remove from the album"
+    )]
+    pub files: Vec<AlbumFile>,
 }
 
 #[derive(Debug, PartialEq, Args)]
 #[command(about = "Delete an album", arg_required_else_help(true))]
 pub struct AlbumDelete {
+    #[arg(long, help = "Also delete the files, except those in other albums in the ledger")]
+    pub with_files: bool,
+
+    #[arg(long, requires = "with_files", help = "Keep local copies of the deleted files")]
+    pub trash: bool,
+
     #[arg(from_global)]
-    pub user_hash: String,
+    pub user_hash: OptionalUserHash,
 
-    #[arg(help = "Album ID")]
-    pub short: String,
+    #[arg(value_parser = album_short, help = "Album ID or URL")]
+    pub short: AlbumShort,
 }
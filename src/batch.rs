@@ -0,0 +1,204 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Album requests split into batches, as Catbox limits the files of one request
+
+use anyhow::{ Result, anyhow, bail };
+use catbox::{
+    album::{ AlbumFile, Uploaded },
+    id::{ AlbumShort, FileId, UserHash },
+    upload::UploadOptions,
+    url::{ album_short, album_url },
+};
+
+#[cfg(not(test))]
+use catbox::album;
+#[cfg(test)]
+use crate::test::catbox::album;
+
+use crate::{ record_album, record_uploads };
+
+/// Number of files deleted with one request
+pub const DELETE_BATCH_SIZE: usize = 100;
+/// Number of files added to or removed from an album with one request
+pub const ALBUM_BATCH_SIZE: usize = 100;
+
+/// Create an album in batches of [`ALBUM_BATCH_SIZE`] files
+///
+/// The album is created with the first batch, then printed and recorded before the others
+/// are added to it, so it is not lost if adding a later batch fails.
+///
+/// Returns the files which were uploaded
+///
+/// # Arguments
+///
+/// * `title` - Title of the album
+/// * `description` - Description of the album
+/// * `user_hash` - User hash, required for more than one batch
+/// * `files` - Files on Catbox and local files to upload, in order
+/// * `options` - Transformations applied to local files before they are uploaded
+pub async fn create(
+    title: &str,
+    description: &str,
+    user_hash: Option<&UserHash>,
+    files: &[AlbumFile],
+    options: &UploadOptions
+) -> Result<Vec<Uploaded>> {
+    // only the first batch is sent anonymously, adding the others requires a user hash
+    if files.len() > ALBUM_BATCH_SIZE && user_hash.is_none() {
+        bail!("Albums with more than {ALBUM_BATCH_SIZE} files require a user hash");
+    }
+
+    let mut batches = files.chunks(ALBUM_BATCH_SIZE);
+    let first = batches.next().unwrap_or_default();
+
+    let created = album::create_from_files(title, description, user_hash, first, options).await?;
+
+    record_uploads(&created.uploaded, options).await;
+    println!("{}", created.response);
+
+    let mut ids = uploaded_ids(first, &created.uploaded);
+    let mut uploaded = created.uploaded;
+
+    record_album(&created.response, title, &ids);
+
+    for batch in batches {
+        let (Some(user_hash), Ok(short)) = (user_hash, album_short(created.response.trim())) else {
+            bail!("Creating the album failed: {}", created.response);
+        };
+
+        let added = album::add_from_files(&short, user_hash, batch, options).await.map_err(|err| {
+            anyhow!("Adding files to {} failed after {} files: {err}", created.response, ids.len())
+        })?;
+
+        record_uploads(&added.uploaded, options).await;
+        ids.extend(uploaded_ids(batch, &added.uploaded));
+        uploaded.extend(added.uploaded);
+
+        record_album(&created.response, title, &ids);
+    }
+
+    Ok(uploaded)
+}
+
+/// Add files to an album in batches of [`ALBUM_BATCH_SIZE`] files
+///
+/// Returns the last response and the IDs of the added files, in order
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+/// * `user_hash` - User hash of the album's owner
+/// * `files` - Files on Catbox and local files to upload, in order
+/// * `options` - Transformations applied to local files before they are uploaded
+pub async fn add(
+    short: &AlbumShort,
+    user_hash: &UserHash,
+    files: &[AlbumFile],
+    options: &UploadOptions
+) -> Result<(String, Vec<FileId>)> {
+    let mut ids = Vec::new();
+    let mut res = String::new();
+
+    for batch in files.chunks(ALBUM_BATCH_SIZE) {
+        let added = album::add_from_files(short, user_hash, batch, options).await?;
+
+        record_uploads(&added.uploaded, options).await;
+        ids.extend(uploaded_ids(batch, &added.uploaded));
+        res = added.response;
+    }
+
+    Ok((res, ids))
+}
+
+/// Files on Catbox as files of an album
+pub fn remote(files: &[FileId]) -> Vec<AlbumFile> {
+    files.iter().cloned().map(AlbumFile::Remote).collect()
+}
+
+/// Replace the title, description and files of an album in batches of [`ALBUM_BATCH_SIZE`] files
+///
+/// The album is edited with the first batch and the others are appended, which keeps the order.
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+/// * `title` - New title of the album
+/// * `description` - New description of the album
+/// * `user_hash` - User hash of the album's owner
+/// * `files` - New files of the album, in order
+pub async fn edit(
+    short: &AlbumShort,
+    title: &str,
+    description: &str,
+    user_hash: &UserHash,
+    files: &[FileId]
+) -> Result<String> {
+    let mut batches = files.chunks(ALBUM_BATCH_SIZE);
+    let first = batches.next().unwrap_or_default();
+
+    let mut res = album::edit(short, title, description, user_hash, first).await?;
+    let mut shown = first.len();
+
+    for batch in batches {
+        res = album::add_files(short, user_hash, batch).await.map_err(|err| {
+            anyhow!("{} only shows {shown} of {} files: {err}", album_url(short), files.len())
+        })?;
+
+        shown += batch.len();
+    }
+
+    Ok(res)
+}
+
+/// IDs of the files of an album after uploading, with local files replaced by their uploads
+pub fn uploaded_ids(files: &[AlbumFile], uploaded: &[Uploaded]) -> Vec<FileId> {
+    // uploads are returned in the order of the local files
+    let mut uploaded = uploaded.iter().map(|uploaded| uploaded.id.clone());
+
+    files
+        .iter()
+        .filter_map(|file| match file {
+            AlbumFile::Remote(id) => Some(id.clone()),
+            AlbumFile::Local(_) => uploaded.next(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ ledger, test };
+    use catbox::url::file_url;
+
+    #[tokio::test]
+    async fn batches() -> Result<()> {
+        let _state = test::isolate();
+
+        let existing = AlbumFile::Remote("abc123.jpg".parse()?);
+        let local = AlbumFile::Local("photo.jpg".to_string());
+        let uploaded = Uploaded {
+            path: "photo.jpg".to_string(),
+            link: file_url("def456.jpg"),
+            id: "def456.jpg".parse()?,
+        };
+        let ids = uploaded_ids(&[local, existing], &[uploaded]);
+        assert_eq!(ids, vec!["def456.jpg".parse()?, "abc123.jpg".parse()?]);
+
+        // large albums are created with the first batch and completed with the others
+        let ids: Vec<FileId> = (0..250)
+            .map(|i| format!("file{i}.png").parse())
+            .collect::<Result<_>>()?;
+        let user_hash: UserHash = "123456".parse()?;
+        let options = UploadOptions::default();
+
+        assert!(create("Large", "", None, &remote(&ids), &options).await.is_err());
+        create("Large", "", Some(&user_hash), &remote(&ids), &options).await?;
+
+        let short = album_short("123435")?;
+        assert_eq!(ledger::find_album(&short)?.map(|entry| entry.files), Some(ids.clone()));
+
+        edit(&short, "Large", "", &user_hash, &ids).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Deletion of albums, optionally with the files no other known album shows
+//!
+//! Other albums are looked up in the ledger, so files shared with albums
+//! created elsewhere cannot be detected.
+
+use anyhow::Result;
+use catbox::{
+    album::AlbumInfo,
+    id::{ AlbumShort, FileId },
+    url::album_url,
+};
+use futures::stream::{ self, StreamExt };
+
+#[cfg(not(test))]
+use catbox::{ album, file };
+#[cfg(test)]
+use crate::test::catbox::{ album, file };
+
+use crate::{ args::{ self, AlbumDelete }, batch::DELETE_BATCH_SIZE, ledger, trash };
+
+/// Delete an album, with the files no other known album shows if requested
+pub async fn delete_album(album_delete_args: AlbumDelete) -> Result<()> {
+    let short = album_delete_args.short;
+    let user_hash = args::required(album_delete_args.user_hash)?;
+
+    // the files have to be known before the album page is gone
+    let files = if album_delete_args.with_files {
+        album_files(&short).await?
+    } else {
+        Vec::new()
+    };
+
+    println!("{}", album::delete(&short, &user_hash).await?);
+
+    if let Err(err) = ledger::record_album_deleted(&short) {
+        println!("Recording the deletion of {} in the ledger failed: {err}", album_url(&short));
+    }
+
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let others = known_albums(Some(&short)).await?;
+    let mut orphans = Vec::new();
+
+    for file in files {
+        match others.iter().find(|album| album.files.contains(&file)) {
+            Some(album) => println!("Keeping {file}, it is still in {}", album.url()),
+            None => orphans.push(file),
+        }
+    }
+
+    if album_delete_args.trash {
+        trash::save_files(&orphans, &others).await?;
+    }
+
+    for batch in orphans.chunks(DELETE_BATCH_SIZE) {
+        println!("{}", file::delete(batch, &user_hash).await?);
+    }
+
+    Ok(())
+}
+
+/// Files of an album from its page, or from the ledger if the page cannot be read
+async fn album_files(short: &AlbumShort) -> Result<Vec<FileId>> {
+    match album::show(short).await {
+        Ok(info) => Ok(info.files),
+        Err(err) => ledger::find_album(short)?.map(|entry| entry.files).ok_or(err),
+    }
+}
+
+/// Current contents of the albums in the ledger, or their recorded files if a page cannot be read
+///
+/// # Arguments
+///
+/// * `except` - Album to leave out
+pub async fn known_albums(except: Option<&AlbumShort>) -> Result<Vec<AlbumInfo>> {
+    let recorded: Vec<_> = ledger::albums()?
+        .into_iter()
+        .filter(|entry| Some(&entry.album) != except)
+        .collect();
+
+    let shown: Vec<_> = stream::iter(&recorded)
+        .map(|entry| album::show(&entry.album))
+        .buffered(10)
+        .collect().await;
+
+    Ok(
+        recorded
+            .into_iter()
+            .zip(shown)
+            .map(|(entry, shown)| {
+                shown.unwrap_or(AlbumInfo {
+                    short: entry.album,
+                    title: entry.title,
+                    description: String::new(),
+                    files: entry.files,
+                })
+            })
+            .collect()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ args::{ CatboxArgs, CatboxCommand }, parse_album, test };
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn album_delete() -> Result<()> {
+        let _state = test::isolate();
+
+        let short: AlbumShort = "zxc789".parse()?;
+        let files: Vec<FileId> = vec!["abc123.jpg".parse()?];
+
+        ledger::record_album(&short, "Kept", &files)?;
+        assert_eq!(ledger::find_album(&short)?.map(|entry| entry.files), Some(files));
+
+        for command in [
+            vec!["catbox", "album", "delete", "--user", "123345", "asd123"],
+            vec!["catbox", "album", "delete", "-u", "1", "--with-files", "asd123"],
+            vec!["catbox", "album", "delete", "-u", "1", "zxc789"],
+        ] {
+            let args = CatboxArgs::parse_from(command);
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                parse_album(album_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        assert!(ledger::find_album(&short)?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Comparison of an album with a local directory or another album
+//!
+//! Files are matched by their Catbox ID first, then by the SHA-256 hash of their contents,
+//! and finally by the local path they were uploaded from. Files matched by path whose
+//! hashes differ are reported as changed.
+
+use anyhow::Result;
+use catbox::{ hash, id::{ AlbumShort, FileId }, url::album_short };
+use serde::Serialize;
+use std::{ fmt, path::Path };
+
+#[cfg(not(test))]
+use catbox::album;
+#[cfg(test)]
+use crate::test::catbox::album;
+
+use crate::{ args::{ AlbumDiff, Format }, ledger, sync };
+
+/// A file on one side of the comparison
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// ID or path shown in the output
+    pub label: String,
+    /// Catbox ID, if the file is on Catbox
+    pub id: Option<String>,
+    /// Hash of the contents, if known
+    pub sha256: Option<String>,
+    /// Local path, if known
+    pub path: Option<String>,
+}
+
+/// A pair of files which were matched by path, but whose contents differ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Changed {
+    pub left: String,
+    pub right: String,
+}
+
+/// Differences between two sides
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Diff {
+    pub left: String,
+    pub right: String,
+    /// Old and new title, if they differ
+    pub title: Option<(String, String)>,
+    /// Old and new description, if they differ
+    pub description: Option<(String, String)>,
+    pub only_left: Vec<String>,
+    pub only_right: Vec<String>,
+    pub changed: Vec<Changed>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() &&
+            self.description.is_none() &&
+            self.only_left.is_empty() &&
+            self.only_right.is_empty() &&
+            self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Comparing {} with {}", self.left, self.right)?;
+
+        if self.is_empty() {
+            return write!(f, "No differences");
+        }
+
+        let mut lines = Vec::new();
+
+        if let Some((left, right)) = &self.title {
+            lines.push(format!("title: {left:?} != {right:?}"));
+        }
+
+        if let Some((left, right)) = &self.description {
+            lines.push(format!("description: {left:?} != {right:?}"));
+        }
+
+        lines.extend(self.only_left.iter().map(|item| format!("< {item}")));
+        lines.extend(self.only_right.iter().map(|item| format!("> {item}")));
+        lines.extend(
+            self.changed.iter().map(|changed| format!("~ {} != {}", changed.left, changed.right))
+        );
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Match the files of two sides
+///
+/// Returns the files only on the left, only on the right, and the changed pairs
+pub fn compare(left: &[Item], right: Vec<Item>) -> (Vec<String>, Vec<String>, Vec<Changed>) {
+    let mut right: Vec<Option<Item>> = right.into_iter().map(Some).collect();
+    let mut only_left = Vec::new();
+    let mut changed = Vec::new();
+
+    let same_id = |a: &Item, b: &Item| a.id.is_some() && a.id == b.id;
+    let same_hash = |a: &Item, b: &Item| a.sha256.is_some() && a.sha256 == b.sha256;
+    let same_path = |a: &Item, b: &Item| a.path.is_some() && a.path == b.path;
+
+    for item in left {
+        let mut take = |matches: &dyn Fn(&Item, &Item) -> bool| {
+            right
+                .iter_mut()
+                .find(|other| other.as_ref().is_some_and(|other| matches(item, other)))
+                .and_then(Option::take)
+        };
+
+        if take(&same_id).is_some() || take(&same_hash).is_some() {
+            continue;
+        }
+
+        match take(&same_path) {
+            Some(other) => changed.push(Changed { left: item.label.clone(), right: other.label }),
+            None => only_left.push(item.label.clone()),
+        }
+    }
+
+    let only_right = right
+        .into_iter()
+        .flatten()
+        .map(|item| item.label)
+        .collect();
+
+    (only_left, only_right, changed)
+}
+
+/// Print the differences between an album and a local directory or another album
+pub async fn diff_album(album_diff_args: AlbumDiff) -> Result<()> {
+    let diff = compare_album(&album_diff_args.short, &album_diff_args.other).await?;
+
+    match album_diff_args.format {
+        Format::Text => println!("{diff}"),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+    }
+
+    Ok(())
+}
+
+/// Compare an album with a local directory, or with another album if `other` is no directory
+async fn compare_album(short: &AlbumShort, other: &str) -> Result<Diff> {
+    let info = album::show(short).await?;
+    let left = album_items(&info.files)?;
+
+    let (right, label, title, description) = if Path::new(other).is_dir() {
+        let sidecar = sync::sidecar(other)?.unwrap_or_default();
+        let mut items = Vec::new();
+
+        for path in sync::local_files(other)? {
+            items.push(Item {
+                sha256: Some(hash::file(&path).await?),
+                path: Some(Path::new(&path).canonicalize()?.to_string_lossy().into_owned()),
+                label: path,
+                ..Default::default()
+            });
+        }
+
+        (items, other.to_string(), sidecar.title, sidecar.description)
+    } else {
+        let other = album::show(&album_short(other)?).await?;
+
+        (album_items(&other.files)?, other.url(), Some(other.title), Some(other.description))
+    };
+
+    let (only_left, only_right, changed) = compare(&left, right);
+
+    Ok(Diff {
+        left: info.url(),
+        right: label,
+        title: title.filter(|title| *title != info.title).map(|title| (info.title, title)),
+        description: description
+            .filter(|description| *description != info.description)
+            .map(|description| (info.description, description)),
+        only_left,
+        only_right,
+        changed,
+    })
+}
+
+/// Files of an album, with the hash and path of their upload if it is in the ledger
+fn album_items(files: &[FileId]) -> Result<Vec<Item>> {
+    files
+        .iter()
+        .map(|id| {
+            let entry = ledger::find_id(id.as_str())?;
+
+            Ok(Item {
+                label: id.to_string(),
+                id: Some(id.to_string()),
+                sha256: entry.as_ref().map(|entry| entry.sha256.clone()),
+                path: entry.map(|entry| entry.path),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ args::{ CatboxArgs, CatboxCommand }, parse_album, test };
+    use catbox::upload::UploadOptions;
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn album_diff() -> Result<()> {
+        let _state = test::isolate();
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("def456.png");
+        let path = dir.path().to_str().unwrap();
+        let short: AlbumShort = "asd123".parse()?;
+
+        std::fs::write(&file, "uploaded")?;
+        std::fs::write(dir.path().join("new.txt"), "new")?;
+        std::fs::write(dir.path().join(sync::SIDECAR), r#"{ "title": "Portfolio" }"#)?;
+
+        let file = file.to_string_lossy().into_owned();
+        crate::upload_file(file.clone(), None, &UploadOptions::default(), false).await;
+
+        let diff = compare_album(&short, path).await?;
+
+        assert_eq!(diff.title, Some(("My album".to_string(), "Portfolio".to_string())));
+        assert_eq!(diff.description, None);
+        assert_eq!(diff.only_left, vec!["abc123.jpg"]);
+        assert_eq!(diff.only_right.len(), 1);
+        assert!(diff.changed.is_empty());
+
+        std::fs::write(&file, "edited")?;
+
+        let diff = compare_album(&short, path).await?;
+
+        let changed = Changed { left: "def456.png".to_string(), right: file };
+        assert_eq!(diff.changed, vec![changed]);
+
+        for format in ["text", "json"] {
+            let args = CatboxArgs::parse_from(
+                vec!["catbox", "album", "diff", "-f", format, "asd123", "catbox.moe/c/qwe456"]
+            );
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                parse_album(album_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        assert!(compare_album(&short, "https://catbox.moe/c/qwe456").await?.is_empty());
+
+        Ok(())
+    }
+}
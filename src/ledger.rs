@@ -0,0 +1,269 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Local record of uploaded files
+//!
+//! Every successful upload of a local file is appended as a line of JSON to the ledger,
+//! so downloads can be verified and local files can be mapped to their Catbox IDs.
+//! Albums created or changed with this tool are recorded as well, so deleting one album
+//! does not delete files which other albums still show.
+//!
+//! The ledger is stored at `$CATBOX_LEDGER`, or `catbox/ledger.jsonl` in the user's data directory.
+
+use anyhow::{ Result, anyhow };
+use catbox::{ dry_run, hash, id::{ AlbumShort, FileId }, upload::UploadOptions, url::Link };
+use serde::{ Deserialize, Serialize, de::DeserializeOwned };
+use std::{
+    fs::{ self, OpenOptions },
+    io::{ ErrorKind, Write },
+    path::{ Path, PathBuf },
+    time::{ SystemTime, UNIX_EPOCH },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Catbox ID of the file, e.g. `abc123.png`
+    pub id: String,
+    /// Link to the file, without encryption key
+    pub url: String,
+    /// Absolute path of the local file
+    pub path: String,
+    /// SHA-256 hash of the local file
+    pub sha256: String,
+    /// Size of the local file in bytes
+    pub size: u64,
+    #[serde(default)]
+    pub encrypted: bool,
+    #[serde(default)]
+    pub compression: Option<String>,
+    /// Whether the uploaded contents differ from the local file, e.g. after stripping metadata
+    #[serde(default)]
+    pub altered: bool,
+    /// Unix timestamp of the upload
+    pub uploaded: u64,
+}
+
+/// State of an album after it was changed with this tool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlbumEntry {
+    /// ID of the album
+    pub album: AlbumShort,
+    pub title: String,
+    /// Catbox IDs of the files, in the order of the album
+    pub files: Vec<FileId>,
+    #[serde(default)]
+    pub deleted: bool,
+    /// Unix timestamp of the change
+    pub recorded: u64,
+}
+
+#[cfg(not(test))]
+pub fn path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os("CATBOX_LEDGER") {
+        return Ok(PathBuf::from(path));
+    }
+
+    Ok(
+        dirs
+            ::data_dir()
+            .ok_or_else(|| anyhow!("Cannot determine the data directory for the ledger"))?
+            .join("catbox")
+            .join("ledger.jsonl")
+    )
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Ledger of the running test, see [`crate::test::isolate`]
+    pub static TEST_PATH: std::cell::RefCell<Option<PathBuf>> = const {
+        std::cell::RefCell::new(None)
+    };
+}
+
+#[cfg(test)]
+pub fn path() -> Result<PathBuf> {
+    TEST_PATH.with_borrow(Clone::clone).ok_or_else(|| anyhow!("The test has no ledger"))
+}
+
+/// Append an entry to the ledger
+pub fn append<T: Serialize>(entry: &T) -> Result<()> {
+    // nothing was sent, so there is nothing to record
+    if dry_run::enabled() {
+        return Ok(());
+    }
+
+    let path = path()?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+/// Read all upload entries, oldest first
+///
+/// Lines which cannot be parsed are skipped.
+pub fn entries() -> Result<Vec<Entry>> {
+    lines()
+}
+
+/// Read all lines of one kind, oldest first, skipping the other kind and invalid lines
+fn lines<T: DeserializeOwned>() -> Result<Vec<T>> {
+    match fs::read_to_string(path()?) {
+        Ok(ledger) =>
+            Ok(
+                ledger
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            ),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Find the latest entry for a Catbox ID
+pub fn find_id(id: &str) -> Result<Option<Entry>> {
+    Ok(entries()?.into_iter().rev().find(|entry| entry.id == id))
+}
+
+/// Find an upload of a local file which can be reused, e.g. for adding it to an album
+///
+/// Only unencrypted and uncompressed uploads to Catbox are reused.
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the local file
+pub async fn find_upload(file_path: &str) -> Result<Option<Entry>> {
+    let sha256 = hash::file(file_path).await?;
+
+    Ok(
+        catbox_entries()?
+            .into_iter()
+            .rev()
+            .find(|entry| entry.sha256 == sha256 && !entry.encrypted && entry.compression.is_none())
+    )
+}
+
+/// Find the latest upload of a local file to Catbox, by its path or its contents
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the local file
+pub async fn find_path(file_path: &str) -> Result<Option<Entry>> {
+    let path = Path::new(file_path).canonicalize()?.to_string_lossy().into_owned();
+    let entries = catbox_entries()?;
+
+    if let Some(entry) = entries.iter().rev().find(|entry| entry.path == path) {
+        return Ok(Some(entry.clone()));
+    }
+
+    let sha256 = hash::file(file_path).await?;
+
+    Ok(entries.into_iter().rev().find(|entry| entry.sha256 == sha256))
+}
+
+/// Entries of files uploaded to Catbox, as files on Litterbox expire
+fn catbox_entries() -> Result<Vec<Entry>> {
+    Ok(
+        entries()?
+            .into_iter()
+            .filter(|entry| matches!(entry.url.parse(), Ok(Link::File(_))))
+            .collect()
+    )
+}
+
+/// Latest state of the albums which were not deleted
+pub fn albums() -> Result<Vec<AlbumEntry>> {
+    let mut albums: Vec<AlbumEntry> = Vec::new();
+
+    for entry in lines::<AlbumEntry>()?.into_iter().rev() {
+        if !albums.iter().any(|album| album.album == entry.album) {
+            albums.push(entry);
+        }
+    }
+
+    albums.retain(|album| !album.deleted);
+
+    Ok(albums)
+}
+
+/// Find the latest state of an album, if it was not deleted
+pub fn find_album(short: &AlbumShort) -> Result<Option<AlbumEntry>> {
+    Ok(albums()?.into_iter().find(|album| album.album == *short))
+}
+
+/// Record the files of an album after it was created or changed
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+/// * `title` - Title of the album
+/// * `files` - Files of the album, in order
+pub fn record_album(short: &AlbumShort, title: &str, files: &[FileId]) -> Result<()> {
+    append(
+        &(AlbumEntry {
+            album: short.clone(),
+            title: title.to_string(),
+            files: files.to_vec(),
+            deleted: false,
+            recorded: now(),
+        })
+    )
+}
+
+/// Record that an album was deleted
+pub fn record_album_deleted(short: &AlbumShort) -> Result<()> {
+    append(
+        &(AlbumEntry {
+            album: short.clone(),
+            title: String::new(),
+            files: Vec::new(),
+            deleted: true,
+            recorded: now(),
+        })
+    )
+}
+
+/// Current Unix timestamp
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+/// Record a successful upload of a local file
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the uploaded file
+/// * `link` - Link returned by the upload, the key in its fragment is not recorded
+/// * `options` - Transformations which were applied to the file
+pub async fn record(file_path: &str, link: &str, options: &UploadOptions) -> Result<()> {
+    let url = link.split_once('#').map_or(link, |(url, _)| url).trim();
+
+    let id = url
+        .rsplit('/')
+        .next()
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| anyhow!("{url} does not point to a file"))?;
+
+    let path = Path::new(file_path).canonicalize()?;
+
+    append(
+        &(Entry {
+            id: id.to_string(),
+            url: url.to_string(),
+            path: path.to_string_lossy().into_owned(),
+            sha256: hash::file(file_path).await?,
+            size: tokio::fs::metadata(file_path).await?.len(),
+            encrypted: options.encrypt,
+            compression: options.compress.map(|compression| compression.to_string()),
+            altered: options.strip_metadata || options.resize.is_some(),
+            uploaded: now(),
+        })
+    )
+}
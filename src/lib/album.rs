@@ -3,11 +3,227 @@
 //! Functions for handling albums through Catbox's API
 //!
 //! Calls API described at <https://catbox.moe/tools.php>.
+//! The API cannot read albums, so their contents are parsed from the public album page.
 
-use anyhow::Result;
+use anyhow::{ Result, anyhow, bail };
+use futures::stream::{ self, StreamExt, TryStreamExt };
+use regex::Regex;
 use reqwest::Client;
+use serde::{ Deserialize, Serialize };
+use std::{ fmt, sync::LazyLock };
 
-use super::{ CATBOX_API_URL, UASTRING };
+use super::{
+    CATBOX_API_URL,
+    UASTRING,
+    dry_run,
+    file,
+    id::{ self, AlbumShort, FileId, UserHash },
+    upload::UploadOptions,
+    url::{ album_url, file_id },
+};
+
+static RE_TITLE: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"(?is)<h2[^>]*>(.*?)</h2>").unwrap()
+);
+static RE_DESCRIPTION: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"(?is)</h2>\s*<p[^>]*>(.*?)</p>").unwrap()
+);
+static RE_META: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r#"(?i)<meta\s+property=["']og:(title|description)["']\s+content=["']([^"']*)["']"#)
+        .unwrap()
+);
+static RE_FILE: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r"https?://files\.catbox\.moe/([A-Za-z0-9_.-]+)").unwrap()
+);
+static RE_GRID: LazyLock<Regex> = LazyLock::new(||
+    Regex::new(r#"(?i)<div\s[^>]*class=["'][^"']*\bimagecontainer\b[^"']*["'][^>]*>"#).unwrap()
+);
+static RE_DIV: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<(/?)div\b").unwrap());
+static RE_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// Number of files uploaded at the same time
+const CONCURRENT_UPLOADS: usize = 10;
+
+/// A file for an album, either already on Catbox or still to be uploaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlbumFile {
+    /// File already on Catbox
+    Remote(FileId),
+    /// Path to a local file
+    Local(String),
+}
+
+/// A local file which was uploaded for an album
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uploaded {
+    /// Path to the local file
+    pub path: String,
+    /// Link returned by the upload
+    pub link: String,
+    pub id: FileId,
+}
+
+/// Result of an album operation which uploaded local files first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumUpload {
+    /// Server response to the album operation
+    pub response: String,
+    /// Files which were uploaded, in the order they were given
+    pub uploaded: Vec<Uploaded>,
+}
+
+/// Contents of an album as shown on its public page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlbumInfo {
+    /// ID of the album
+    pub short: AlbumShort,
+    pub title: String,
+    pub description: String,
+    /// Catbox IDs of the files, in the order of the album
+    pub files: Vec<FileId>,
+}
+
+impl AlbumInfo {
+    /// Link to the album page
+    pub fn url(&self) -> String {
+        album_url(&self.short)
+    }
+}
+
+/// Differences between two states of an album
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Changes {
+    /// Old and new title, if it changed
+    pub title: Option<(String, String)>,
+    /// Old and new description, if it changed
+    pub description: Option<(String, String)>,
+    /// Files only in the new state
+    pub added: Vec<FileId>,
+    /// Files only in the old state
+    pub removed: Vec<FileId>,
+}
+
+impl Changes {
+    /// Compare two states of an album
+    ///
+    /// # Arguments
+    ///
+    /// * `old` - Album before the changes
+    /// * `new` - Album after the changes
+    pub fn between(old: &AlbumInfo, new: &AlbumInfo) -> Self {
+        let changed = |old: &String, new: &String| {
+            (old != new).then(|| (old.clone(), new.clone()))
+        };
+
+        Self {
+            title: changed(&old.title, &new.title),
+            description: changed(&old.description, &new.description),
+            added: new.files
+                .iter()
+                .filter(|file| !old.files.contains(file))
+                .cloned()
+                .collect(),
+            removed: old.files
+                .iter()
+                .filter(|file| !new.files.contains(file))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Whether both states are the same, ignoring the order of files
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() &&
+            self.description.is_none() &&
+            self.added.is_empty() &&
+            self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Vec::new();
+
+        if let Some((old, new)) = &self.title {
+            lines.push(format!("title: {old:?} -> {new:?}"));
+        }
+
+        if let Some((old, new)) = &self.description {
+            lines.push(format!("description: {old:?} -> {new:?}"));
+        }
+
+        lines.extend(self.added.iter().map(|file| format!("+ {file}")));
+        lines.extend(self.removed.iter().map(|file| format!("- {file}")));
+
+        f.write_str(&lines.join("\n"))
+    }
+}
+
+/// How the files of an album are divided into new albums
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    /// One album per file extension
+    Extension,
+    /// Albums with up to the given number of files
+    Count(usize),
+}
+
+/// Combine the files of several albums, keeping the first occurrence of each file
+///
+/// # Arguments
+///
+/// * `albums` - Albums to combine, in order
+pub fn merge(albums: &[AlbumInfo]) -> Vec<FileId> {
+    let mut files: Vec<FileId> = Vec::new();
+
+    for file in albums.iter().flat_map(|album| &album.files) {
+        if !files.contains(file) {
+            files.push(file.clone());
+        }
+    }
+
+    files
+}
+
+/// Divide files into groups, keeping their order
+///
+/// Returns the groups with a label for each, e.g. `png` or `2/3`
+///
+/// # Arguments
+///
+/// * `files` - Files to divide
+/// * `by` - How to divide the files
+pub fn split(files: &[FileId], by: Split) -> Vec<(String, Vec<FileId>)> {
+    match by {
+        Split::Extension => {
+            let mut groups: Vec<(String, Vec<FileId>)> = Vec::new();
+
+            for file in files {
+                let extension = file
+                    .as_str()
+                    .rsplit_once('.')
+                    .map_or_else(|| "none".to_string(), |(_, ext)| ext.to_ascii_lowercase());
+
+                match groups.iter_mut().find(|(label, _)| *label == extension) {
+                    Some((_, group)) => group.push(file.clone()),
+                    None => groups.push((extension, vec![file.clone()])),
+                }
+            }
+
+            groups
+        }
+        Split::Count(count) => {
+            let chunks: Vec<_> = files.chunks(count.max(1)).collect();
+            let total = chunks.len();
+
+            chunks
+                .into_iter()
+                .enumerate()
+                .map(|(i, chunk)| (format!("{}/{total}", i + 1), chunk.to_vec()))
+                .collect()
+        }
+    }
+}
 
 /// Create a new album
 ///
@@ -22,19 +238,22 @@ use super::{ CATBOX_API_URL, UASTRING };
 pub async fn create<S: Into<String>>(
     title: S,
     desc: S,
-    user_hash: S,
-    files: Vec<S>
+    user_hash: Option<&UserHash>,
+    files: &[FileId]
 ) -> Result<String> {
-    let files: Vec<_> = files.into_iter().map(Into::into).collect();
-
     let form = [
         ("reqtype", "createalbum"),
-        ("userhash", &user_hash.into()),
+        ("userhash", id::user_hash(user_hash)),
         ("title", &title.into()),
         ("desc", &desc.into()),
-        ("files", &files.join(" ")),
+        ("files", &id::join(files)),
     ];
 
+    if dry_run::enabled() {
+        dry_run::report(CATBOX_API_URL, &form, None);
+        return Ok(dry_run::album_link());
+    }
+
     Ok(
         Client::builder()
             .user_agent(UASTRING)
@@ -47,10 +266,163 @@ pub async fn create<S: Into<String>>(
     )
 }
 
+/// Create a new album from files on Catbox and local files
+///
+/// Local files are uploaded first. If an upload or the creation of the album fails,
+/// files which were already uploaded are deleted again, which requires a user hash.
+///
+/// Returns the server response and the uploaded files
+///
+/// # Arguments
+///
+/// * `title` - Album title
+/// * `desc` - Album description
+/// * `user_hash` - User account hash, required for rolling back uploads. Otherwise optional.
+/// * `files` - Files for the album, in album order
+/// * `options` - Transformations applied to local files before they are uploaded
+pub async fn create_from_files<S: Into<String>>(
+    title: S,
+    desc: S,
+    user_hash: Option<&UserHash>,
+    files: &[AlbumFile],
+    options: &UploadOptions
+) -> Result<AlbumUpload> {
+    let (ids, uploaded) = upload_files(files, user_hash, options).await?;
+
+    let err = match create(title, desc, user_hash, &ids).await {
+        Ok(response) if response.starts_with("http") => {
+            return Ok(AlbumUpload { response, uploaded });
+        }
+        Ok(response) => anyhow!("Creating the album failed: {response}"),
+        Err(err) => anyhow!("Creating the album failed: {err}"),
+    };
+
+    Err(rollback(err, &uploaded, user_hash).await)
+}
+
+/// Add files on Catbox and local files to an album
+///
+/// Local files are uploaded first. If an upload or adding the files fails,
+/// files which were already uploaded are deleted again.
+///
+/// Returns the server response and the uploaded files
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+/// * `user_hash` - User account hash, required for adding files to albums.
+/// * `files` - Files to add to the album
+/// * `options` - Transformations applied to local files before they are uploaded
+pub async fn add_from_files(
+    short: &AlbumShort,
+    user_hash: &UserHash,
+    files: &[AlbumFile],
+    options: &UploadOptions
+) -> Result<AlbumUpload> {
+    let (ids, uploaded) = upload_files(files, Some(user_hash), options).await?;
+
+    let err = match add_files(short, user_hash, &ids).await {
+        Ok(response) if response.starts_with("http") => {
+            return Ok(AlbumUpload { response, uploaded });
+        }
+        Ok(response) => anyhow!("Adding the files to {short} failed: {response}"),
+        Err(err) => anyhow!("Adding the files to {short} failed: {err}"),
+    };
+
+    Err(rollback(err, &uploaded, Some(user_hash)).await)
+}
+
+/// Upload the local files of an album without changing any album
+///
+/// If an upload fails, files which were already uploaded are deleted again,
+/// which requires a user hash.
+///
+/// Returns the IDs of all files, in order, and the uploaded files
+///
+/// # Arguments
+///
+/// * `files` - Files on Catbox and local files to upload
+/// * `user_hash` - User account hash, required for rolling back uploads. Otherwise optional.
+/// * `options` - Transformations applied to local files before they are uploaded
+pub async fn upload_files(
+    files: &[AlbumFile],
+    user_hash: Option<&UserHash>,
+    options: &UploadOptions
+) -> Result<(Vec<FileId>, Vec<Uploaded>)> {
+    let results: Vec<_> = stream
+        ::iter(files)
+        .map(|file| async move {
+            match file {
+                AlbumFile::Remote(id) => Ok((id.clone(), None)),
+                AlbumFile::Local(path) => {
+                    let link = file::from_file_with(path.as_str(), user_hash, options).await?;
+
+                    if !link.starts_with("http") {
+                        bail!("Uploading {path} failed: {link}");
+                    }
+
+                    let id = file_id(&link)?;
+                    Ok((id.clone(), Some(Uploaded { path: path.clone(), link, id })))
+                }
+            }
+        })
+        .buffered(CONCURRENT_UPLOADS)
+        .collect().await;
+
+    let uploaded: Vec<_> = results
+        .iter()
+        .filter_map(|result| result.as_ref().ok().and_then(|(_, uploaded)| uploaded.clone()))
+        .collect();
+
+    match stream::iter(results).try_collect::<Vec<_>>().await {
+        Ok(results) => Ok((results.into_iter().map(|(id, _)| id).collect(), uploaded)),
+        Err(err) => Err(rollback(err, &uploaded, user_hash).await),
+    }
+}
+
+/// Delete uploaded files after a failed album operation
+///
+/// Returns the original error, with a note about the uploaded files
+async fn rollback(
+    err: anyhow::Error,
+    uploaded: &[Uploaded],
+    user_hash: Option<&UserHash>
+) -> anyhow::Error {
+    if uploaded.is_empty() {
+        return err;
+    }
+
+    let ids: Vec<_> = uploaded.iter().map(|uploaded| uploaded.id.clone()).collect();
+
+    let note = match user_hash {
+        Some(user_hash) => deleted_note(&ids, file::delete(&ids, user_hash).await),
+        None => format!("kept {}, deleting uploaded files requires a user hash", id::join(&ids)),
+    };
+
+    anyhow!("{err} ({note})")
+}
+
+/// Describe the outcome of deleting uploaded files again
+///
+/// Catbox answers failed deletions, e.g. with a wrong user hash, with an error message
+/// instead of an error status, so only its success message counts as deleted.
+fn deleted_note(ids: &[FileId], response: Result<String>) -> String {
+    match response {
+        Ok(response) if response.trim() == file::DELETED => {
+            format!("deleted {} uploaded files again", ids.len())
+        }
+        Ok(response) => {
+            format!("kept {}, deleting them failed: {}", id::join(ids), response.trim())
+        }
+        Err(err) => format!("kept {}, deleting them failed: {err}", id::join(ids)),
+    }
+}
+
 /// Edit an album
 ///
 /// **NOTE:** Old album will be "overwritten" with the new information.
-/// Include everything you want the album to have in the call.
+/// Include everything you want the album to have in the call,
+/// or read the current state with [`show`] first.
 ///
 /// Returns an URL to the album
 ///
@@ -62,23 +434,26 @@ pub async fn create<S: Into<String>>(
 /// * `files` - List of existing files on Catbox to be included in the album
 /// * `user_hash` - User account hash, required for edits.
 pub async fn edit<S: Into<String>>(
-    short: S,
+    short: &AlbumShort,
     title: S,
     desc: S,
-    user_hash: S,
-    files: Vec<S>
+    user_hash: &UserHash,
+    files: &[FileId]
 ) -> Result<String> {
-    let files: Vec<_> = files.into_iter().map(Into::into).collect();
-
     let form = [
         ("reqtype", "editalbum"),
-        ("userhash", &user_hash.into()),
-        ("short", &short.into()),
+        ("userhash", user_hash.as_str()),
+        ("short", short.as_str()),
         ("title", &title.into()),
         ("desc", &desc.into()),
-        ("files", &files.join(" ")),
+        ("files", &id::join(files)),
     ];
 
+    if dry_run::enabled() {
+        dry_run::report(CATBOX_API_URL, &form, None);
+        return Ok(album_url(short));
+    }
+
     Ok(
         Client::builder()
             .user_agent(UASTRING)
@@ -100,16 +475,23 @@ pub async fn edit<S: Into<String>>(
 /// * `short` - ID of the album
 /// * `user_hash` - User account hash, required for adding files to albums.
 /// * `files` - List of existing files on Catbox to be added to the album
-pub async fn add_files<S: Into<String>>(short: S, user_hash: S, files: Vec<S>) -> Result<String> {
-    let files: Vec<_> = files.into_iter().map(Into::into).collect();
-
+pub async fn add_files(
+    short: &AlbumShort,
+    user_hash: &UserHash,
+    files: &[FileId]
+) -> Result<String> {
     let form = [
         ("reqtype", "addtoalbum"),
-        ("short", &short.into()),
-        ("userhash", &user_hash.into()),
-        ("files", &files.join(" ")),
+        ("short", short.as_str()),
+        ("userhash", user_hash.as_str()),
+        ("files", &id::join(files)),
     ];
 
+    if dry_run::enabled() {
+        dry_run::report(CATBOX_API_URL, &form, None);
+        return Ok(album_url(short));
+    }
+
     Ok(
         Client::builder()
             .user_agent(UASTRING)
@@ -131,20 +513,23 @@ pub async fn add_files<S: Into<String>>(short: S, user_hash: S, files: Vec<S>) -
 /// * `short` - ID of the album
 /// * `user_hash` - User account hash, required for removing files from albums.
 /// * `files` - List of existing files on Catbox to be removed from the album
-pub async fn remove_files<S: Into<String>>(
-    short: S,
-    user_hash: S,
-    files: Vec<S>
+pub async fn remove_files(
+    short: &AlbumShort,
+    user_hash: &UserHash,
+    files: &[FileId]
 ) -> Result<String> {
-    let files: Vec<_> = files.into_iter().map(Into::into).collect();
-
     let form = [
         ("reqtype", "removefromalbum"),
-        ("userhash", &user_hash.into()),
-        ("short", &short.into()),
-        ("files", &files.join(" ")),
+        ("userhash", user_hash.as_str()),
+        ("short", short.as_str()),
+        ("files", &id::join(files)),
     ];
 
+    if dry_run::enabled() {
+        dry_run::report(CATBOX_API_URL, &form, None);
+        return Ok(album_url(short));
+    }
+
     Ok(
         Client::builder()
             .user_agent(UASTRING)
@@ -165,13 +550,18 @@ pub async fn remove_files<S: Into<String>>(
 ///
 /// * `short` - ID of the album
 /// * `user_hash` - User account hash, required for deleting albums.
-pub async fn delete<S: Into<String>>(short: S, user_hash: S) -> Result<String> {
+pub async fn delete(short: &AlbumShort, user_hash: &UserHash) -> Result<String> {
     let form = [
         ("reqtype", "deletealbum"),
-        ("userhash", &user_hash.into()),
-        ("short", &short.into()),
+        ("userhash", user_hash.as_str()),
+        ("short", short.as_str()),
     ];
 
+    if dry_run::enabled() {
+        dry_run::report(CATBOX_API_URL, &form, None);
+        return Ok(String::new());
+    }
+
     Ok(
         Client::builder()
             .user_agent(UASTRING)
@@ -183,3 +573,247 @@ pub async fn delete<S: Into<String>>(short: S, user_hash: S) -> Result<String> {
             .text().await?
     )
 }
+
+/// Read an album from its public page
+///
+/// Returns the title, description and files of the album
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+pub async fn show(short: &AlbumShort) -> Result<AlbumInfo> {
+    let page = Client::builder()
+        .user_agent(UASTRING)
+        .build()
+        .unwrap_or_else(|_| Client::new())
+        .get(album_url(short))
+        .send().await?
+        .error_for_status()?
+        .text().await?;
+
+    parse_page(short, &page)
+}
+
+/// Parse the public page of an album
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+/// * `page` - HTML of the album page
+pub fn parse_page(short: &AlbumShort, page: &str) -> Result<AlbumInfo> {
+    let meta = |name: &str| {
+        RE_META.captures_iter(page)
+            .find(|captures| captures[1].eq_ignore_ascii_case(name))
+            .map(|captures| text(&captures[2]))
+    };
+
+    let title = RE_TITLE.captures(page)
+        .map(|captures| text(&captures[1]))
+        .or_else(|| meta("title"))
+        .unwrap_or_default();
+
+    let description = RE_DESCRIPTION.captures(page)
+        .map(|captures| text(&captures[1]))
+        .or_else(|| meta("description"))
+        .unwrap_or_default();
+
+    // links in the description and the preview image are not files of the album
+    let Some(grid) = file_grid(page) else {
+        if title.is_empty() {
+            bail!("Album {short} not found");
+        }
+
+        bail!("The files of album {short} were not found on its page");
+    };
+
+    let mut files: Vec<FileId> = Vec::new();
+
+    for file in RE_FILE.captures_iter(grid).filter_map(|captures| captures[1].parse().ok()) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+
+    Ok(AlbumInfo { short: short.clone(), title, description, files })
+}
+
+/// Contents of the element listing the files of an album, up to its closing tag
+fn file_grid(page: &str) -> Option<&str> {
+    let start = RE_GRID.find(page)?.end();
+    let mut depth = 0;
+
+    for tag in RE_DIV.captures_iter(&page[start..]) {
+        if tag[1].is_empty() {
+            depth += 1;
+        } else if depth == 0 {
+            return Some(&page[start..start + tag.get(0)?.start()]);
+        } else {
+            depth -= 1;
+        }
+    }
+
+    None
+}
+
+/// Strip tags and decode entities of an HTML fragment
+fn text(html: &str) -> String {
+    let stripped = RE_TAG.replace_all(html, "");
+    let mut text = String::with_capacity(stripped.len());
+    let mut rest = stripped.as_ref();
+
+    while let Some(start) = rest.find('&') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+
+        let decoded = entity.and_then(|(entity, end)| {
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ =>
+                    entity
+                        .strip_prefix("#x")
+                        .or_else(|| entity.strip_prefix("#X"))
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                        .and_then(char::from_u32),
+            };
+
+            c.map(|c| (c, end))
+        });
+
+        if let Some((c, end)) = decoded {
+            text.push(c);
+            rest = &rest[end + 1..];
+        } else {
+            text.push('&');
+            rest = &rest[1..];
+        }
+    }
+
+    text.push_str(rest);
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::url::file_url;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const PAGE: &str = include_str!("testdata/album.html");
+
+    #[test]
+    fn parse_album_page() -> Result<()> {
+        let short: AlbumShort = "abc123".parse()?;
+        let info = parse_page(&short, PAGE)?;
+
+        assert_eq!(info.title, "Holiday & Friends");
+        assert_eq!(
+            info.description,
+            "Photos from the trip, more at https://files.catbox.moe/other9.zip"
+        );
+        assert_eq!(
+            info.files,
+            vec!["abc123.jpg".parse()?, "def456.mp4".parse()?, "ghi789.pdf".parse()?]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_page_without_files() -> Result<()> {
+        let short: AlbumShort = "abc123".parse()?;
+
+        let empty = r#"<div class="title"><h2>Empty</h2></div><div class="imagecontainer"></div>"#;
+
+        let info = parse_page(&short, empty)?;
+        assert_eq!(info.title, "Empty");
+        assert!(info.files.is_empty());
+
+        // a changed layout must not look like an empty album
+        let changed = PAGE.replace("imagecontainer", "gallery");
+        assert!(parse_page(&short, &changed).is_err());
+
+        assert!(parse_page(&short, "<html><body>Not found</body></html>").is_err());
+
+        Ok(())
+    }
+
+    // the uploads and deletions only print their requests
+    #[tokio::test]
+    async fn rollback_failed_upload() -> Result<()> {
+        dry_run::set(true);
+
+        let dir = tempdir()?;
+        let uploaded = dir.path().join("uploaded.png");
+        fs::write(&uploaded, "image")?;
+
+        let user_hash: UserHash = "123456".parse()?;
+        let files = [
+            AlbumFile::Local(uploaded.to_string_lossy().into_owned()),
+            AlbumFile::Remote("abc123.jpg".parse()?),
+        ];
+
+        let (ids, done) = upload_files(&files, Some(&user_hash), &UploadOptions::default()).await?;
+        assert_eq!(ids, vec!["dryrun.png".parse()?, "abc123.jpg".parse()?]);
+        assert_eq!(done.len(), 1);
+
+        let missing = dir.path().join("missing.png").to_string_lossy().into_owned();
+        let files = [files[0].clone(), AlbumFile::Local(missing)];
+
+        let err = upload_files(&files, Some(&user_hash), &UploadOptions::default()).await;
+        assert!(err.unwrap_err().to_string().ends_with("(deleted 1 uploaded files again)"));
+
+        // Catbox reports failed deletions in the response text
+        let ids = [done[0].id.clone()];
+        let note = deleted_note(&ids, Ok("Files don't exist or userhash is wrong.".to_string()));
+        assert!(note.starts_with("kept dryrun.png, deleting them failed: "), "{note}");
+        assert!(deleted_note(&ids, Err(anyhow!("timeout"))).starts_with("kept dryrun.png"));
+        let note = deleted_note(&ids, Ok(file::DELETED.to_string()));
+        assert_eq!(note, "deleted 1 uploaded files again");
+
+        let err = upload_files(&files, None, &UploadOptions::default()).await;
+        assert!(
+            err.unwrap_err().to_string()
+                .ends_with("(kept dryrun.png, deleting uploaded files requires a user hash)")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rollback_failed_creation() -> Result<()> {
+        dry_run::set(true);
+
+        let user_hash: UserHash = "123456".parse()?;
+        let uploaded: Vec<_> = ["a.png", "b.jpg"]
+            .into_iter()
+            .map(|id| Uploaded { path: id.into(), link: file_url(id), id: id.parse().unwrap() })
+            .collect();
+
+        let failed = || anyhow!("Creating the album failed: Internal error");
+
+        assert_eq!(
+            rollback(failed(), &uploaded, Some(&user_hash)).await.to_string(),
+            "Creating the album failed: Internal error (deleted 2 uploaded files again)"
+        );
+        assert_eq!(
+            rollback(failed(), &uploaded, None).await.to_string(),
+            "Creating the album failed: Internal error \
+            (kept a.png b.jpg, deleting uploaded files requires a user hash)"
+        );
+        assert_eq!(
+            rollback(failed(), &[], Some(&user_hash)).await.to_string(),
+            "Creating the album failed: Internal error"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,363 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Client-side encryption of uploads
+//!
+//! Files are encrypted with AES-256-GCM in chunks of 64 KiB, so they can be streamed
+//! into the upload form without being held in memory. AES-GCM is used because it is
+//! also available to browsers through WebCrypto.
+//!
+//! Layout of an encrypted file:
+//! * 4 byte magic `CBX1`
+//! * 7 byte random nonce prefix
+//! * chunks of up to 64 KiB plaintext, each followed by its 16 byte tag
+//!
+//! The nonce of every chunk is the prefix, followed by the chunk index as a big-endian
+//! `u32` and a final byte which is `1` for the last chunk and `0` otherwise.
+//! This prevents chunks from being reordered, dropped or truncated unnoticed.
+//!
+//! [`share_page`] builds a standalone HTML page which decrypts an upload in the browser.
+
+use aes_gcm::{ Aes256Gcm, Nonce, aead::{ Aead, KeyInit, OsRng, rand_core::RngCore } };
+use anyhow::{ Result, anyhow, bail };
+use base64::{ Engine, engine::general_purpose::URL_SAFE_NO_PAD };
+use futures::{ Stream, stream };
+use std::io;
+use tokio::io::{ AsyncRead, AsyncReadExt };
+
+/// Magic bytes at the start of every encrypted file
+pub const MAGIC: &[u8; 4] = b"CBX1";
+/// Size of the plaintext in a single chunk
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// Extension appended to the names of encrypted uploads
+pub const EXTENSION: &str = "enc";
+
+const SHARE_PAGE: &str = include_str!("share.html");
+const PREFIX_LEN: usize = 7;
+const HEADER_LEN: usize = MAGIC.len() + PREFIX_LEN;
+const TAG_LEN: usize = 16;
+
+/// Key used to encrypt a single upload
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key([u8; 32]);
+
+impl Key {
+    /// Generate a new random key
+    pub fn generate() -> Self {
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// Encode the key for use as a URL fragment
+    pub fn to_fragment(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.0)
+    }
+
+    /// Decode a key from a URL fragment
+    ///
+    /// # Arguments
+    ///
+    /// * `fragment` - Fragment of a link returned by an encrypted upload, without the `#`
+    pub fn from_fragment(fragment: &str) -> Result<Self> {
+        let key = URL_SAFE_NO_PAD.decode(fragment)?;
+
+        Ok(Self(key.try_into().map_err(|_| anyhow!("Encryption key must be 32 bytes"))?))
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(<redacted>)")
+    }
+}
+
+fn nonce(prefix: &[u8; PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+async fn read_chunk<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+
+    while chunk.len() < CHUNK_SIZE {
+        let read = (&mut *reader)
+            .take((CHUNK_SIZE - chunk.len()) as u64)
+            .read_to_end(&mut chunk).await?;
+
+        if read == 0 {
+            break;
+        }
+    }
+
+    Ok(chunk)
+}
+
+struct Encryptor<R> {
+    reader: R,
+    cipher: Aes256Gcm,
+    prefix: [u8; PREFIX_LEN],
+    counter: u32,
+    header_sent: bool,
+    next: Option<Vec<u8>>,
+    done: bool,
+}
+
+/// Encrypt everything read from `reader` into a stream of ciphertext
+///
+/// # Arguments
+///
+/// * `reader` - Source of the plaintext
+/// * `key` - Key to encrypt with
+pub fn encrypt_stream<R: AsyncRead + Unpin + Send + 'static>(
+    reader: R,
+    key: &Key
+) -> impl Stream<Item = io::Result<Vec<u8>>> + Send + 'static {
+    let mut prefix = [0; PREFIX_LEN];
+    OsRng.fill_bytes(&mut prefix);
+
+    let state = Encryptor {
+        reader,
+        cipher: key.cipher(),
+        prefix,
+        counter: 0,
+        header_sent: false,
+        next: None,
+        done: false,
+    };
+
+    stream::try_unfold(state, |mut state| async move {
+        if !state.header_sent {
+            state.header_sent = true;
+            return Ok(Some(([MAGIC.as_slice(), &state.prefix].concat(), state)));
+        }
+
+        if state.done {
+            return Ok(None);
+        }
+
+        let chunk = match state.next.take() {
+            Some(chunk) => chunk,
+            None => read_chunk(&mut state.reader).await?,
+        };
+
+        let last = if chunk.len() < CHUNK_SIZE {
+            true
+        } else {
+            let next = read_chunk(&mut state.reader).await?;
+            let last = next.is_empty();
+            state.next = Some(next).filter(|next| !next.is_empty());
+            last
+        };
+
+        let ciphertext = state.cipher
+            .encrypt(Nonce::from_slice(&nonce(&state.prefix, state.counter, last)), &chunk[..])
+            .map_err(|_| io::Error::other("Encrypting chunk failed"))?;
+
+        state.counter = state.counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("File too large to encrypt"))?;
+        state.done = last;
+
+        Ok(Some((ciphertext, state)))
+    })
+}
+
+/// Incremental decryption of data produced by [`encrypt_stream`]
+pub struct Decryptor {
+    cipher: Aes256Gcm,
+    prefix: Option<[u8; PREFIX_LEN]>,
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl Decryptor {
+    /// Create a decryptor for a single encrypted file
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Key the file was encrypted with
+    pub fn new(key: &Key) -> Self {
+        Self { cipher: key.cipher(), prefix: None, counter: 0, buffer: Vec::new() }
+    }
+
+    /// Feed ciphertext into the decryptor
+    ///
+    /// Returns all plaintext that can be decrypted so far
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Next piece of the ciphertext
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        if self.prefix.is_none() {
+            if self.buffer.len() < HEADER_LEN {
+                return Ok(Vec::new());
+            }
+
+            self.read_header()?;
+        }
+
+        let mut plaintext = Vec::new();
+
+        // the last chunk can only be identified once the input is complete
+        while self.buffer.len() > CHUNK_SIZE + TAG_LEN {
+            let chunk: Vec<_> = self.buffer.drain(..CHUNK_SIZE + TAG_LEN).collect();
+            plaintext.extend(self.decrypt_chunk(&chunk, false)?);
+        }
+
+        Ok(plaintext)
+    }
+
+    /// Decrypt the final chunk once all ciphertext has been fed into the decryptor
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        if self.prefix.is_none() {
+            self.read_header()?;
+        }
+
+        let chunk = std::mem::take(&mut self.buffer);
+
+        self.decrypt_chunk(&chunk, true)
+    }
+
+    fn read_header(&mut self) -> Result<()> {
+        if self.buffer.len() < HEADER_LEN || &self.buffer[..MAGIC.len()] != MAGIC {
+            bail!("Not an encrypted Catbox upload");
+        }
+
+        let header: Vec<_> = self.buffer.drain(..HEADER_LEN).collect();
+        self.prefix = Some(header[MAGIC.len()..].try_into()?);
+
+        Ok(())
+    }
+
+    fn decrypt_chunk(&mut self, chunk: &[u8], last: bool) -> Result<Vec<u8>> {
+        let prefix = self.prefix.ok_or_else(|| anyhow!("Missing encryption header"))?;
+
+        let plaintext = self.cipher
+            .decrypt(Nonce::from_slice(&nonce(&prefix, self.counter, last)), chunk)
+            .map_err(|_| anyhow!("Decryption failed: wrong key or corrupted file"))?;
+
+        self.counter = self.counter.checked_add(1).ok_or_else(|| anyhow!("Too many chunks"))?;
+
+        Ok(plaintext)
+    }
+}
+
+/// Build a standalone HTML page which downloads and decrypts an encrypted upload
+///
+/// The page reads the key from its own URL fragment, so it has to be opened as `page.html#key`.
+///
+/// # Arguments
+///
+/// * `file_url` - Link to the encrypted file, without the key
+/// * `file_name` - Name the decrypted file is saved as
+pub fn share_page(file_url: &str, file_name: &str) -> String {
+    SHARE_PAGE
+        .replace("{{FILE_URL}}", &js_string(file_url))
+        .replace("{{FILE_NAME}}", &js_string(file_name))
+}
+
+/// Quote a string as a JavaScript literal which is safe to embed in a `<script>` element
+fn js_string(value: &str) -> String {
+    let mut quoted = String::from('"');
+
+    for c in value.chars() {
+        match c {
+            '"' | '\\' | '<' | '>' | '&' | '\u{2028}' | '\u{2029}' | '\0'..='\u{1f}' => {
+                quoted.push_str(&format!("\\u{:04x}", u32::from(c)));
+            }
+            _ => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use std::io::Cursor;
+
+    const CHUNK: usize = CHUNK_SIZE + TAG_LEN;
+
+    async fn encrypt(data: &[u8], key: &Key) -> Result<Vec<u8>> {
+        let chunks: Vec<_> = encrypt_stream(Cursor::new(data.to_vec()), key).try_collect().await?;
+
+        Ok(chunks.concat())
+    }
+
+    /// Decrypt in pieces which do not line up with the chunks
+    fn decrypt(data: &[u8], key: &Key) -> Result<Vec<u8>> {
+        let mut decryptor = Decryptor::new(key);
+        let mut plaintext = Vec::new();
+
+        for piece in data.chunks(10_000) {
+            plaintext.extend(decryptor.update(piece)?);
+        }
+
+        plaintext.extend(decryptor.finish()?);
+
+        Ok(plaintext)
+    }
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn round_trip() -> Result<()> {
+        let key = Key::generate();
+
+        let lens = [
+            0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 2 * CHUNK_SIZE, 5 * CHUNK_SIZE / 2,
+        ];
+
+        for len in lens {
+            let data = data(len);
+            let encrypted = encrypt(&data, &key).await?;
+            let chunks = len.div_ceil(CHUNK_SIZE).max(1);
+
+            assert_eq!(encrypted.len(), HEADER_LEN + len + chunks * TAG_LEN);
+            assert_eq!(decrypt(&encrypted, &key)?, data, "{len} bytes");
+        }
+
+        let key = Key::from_fragment(&key.to_fragment())?;
+        assert_eq!(decrypt(&encrypt(b"fragment", &key).await?, &key)?, b"fragment");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_tampering() -> Result<()> {
+        let key = Key::generate();
+        let encrypted = encrypt(&data(5 * CHUNK_SIZE / 2), &key).await?;
+
+        // dropping the last chunk, or a part of it
+        assert!(decrypt(&encrypted[..HEADER_LEN + 2 * CHUNK], &key).is_err());
+        assert!(decrypt(&encrypted[..encrypted.len() - 1], &key).is_err());
+
+        // swapping the first two chunks
+        let (header, chunks) = encrypted.split_at(HEADER_LEN);
+        let reordered = [header, &chunks[CHUNK..2 * CHUNK], &chunks[..CHUNK], &chunks[2 * CHUNK..]];
+        assert!(decrypt(&reordered.concat(), &key).is_err());
+
+        let mut flipped = encrypted.clone();
+        flipped[HEADER_LEN + 10] ^= 1;
+        assert!(decrypt(&flipped, &key).is_err());
+
+        assert!(decrypt(&encrypted, &Key::generate()).is_err());
+        assert!(decrypt(b"not encrypted", &key).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,420 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Functions for downloading files from Catbox and Litterbox
+//!
+//! Files are downloaded into a `.part` file next to the destination first.
+//! Interrupted downloads are resumed from there with an HTTP range request.
+
+use anyhow::{ Result, anyhow };
+use async_compression::tokio::write::{ GzipDecoder, ZstdDecoder };
+use reqwest::{
+    Client,
+    StatusCode,
+    header::{ CONTENT_LENGTH, CONTENT_RANGE, LAST_MODIFIED, RANGE },
+};
+use std::path::{ Path, PathBuf };
+use tokio::{ fs::{ self, File, OpenOptions }, io::{ AsyncReadExt, AsyncWrite, AsyncWriteExt } };
+use url::Url;
+
+use crate::{
+    UASTRING,
+    crypt::{ self, Decryptor, Key },
+    upload::Compression,
+    url::{ Link, file_url },
+};
+
+/// Transformations applied to a file while it is downloaded
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DownloadOptions {
+    /// Decompress files uploaded with [`crate::upload::UploadOptions::compress`]
+    pub decompress: bool,
+    /// Replace existing files instead of skipping them
+    pub overwrite: bool,
+    /// Name to save the file as instead of the name from the URL
+    pub name: Option<String>,
+}
+
+/// Result of a download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Downloaded {
+    /// Path of the downloaded file
+    pub path: String,
+    /// Whether the file already existed and was not downloaded again
+    pub skipped: bool,
+    /// Number of bytes taken over from an earlier, interrupted download
+    pub resumed: u64,
+    /// Whether the file was decrypted or decompressed
+    pub decoded: bool,
+}
+
+/// Size, modification date and beginning of a remote file
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Peek {
+    /// Size of the whole file in bytes
+    pub size: Option<u64>,
+    /// Unix timestamp of the last modification, i.e. the upload for files on Catbox
+    pub modified: Option<u64>,
+    /// First bytes of the file
+    pub head: Vec<u8>,
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Build the URL of a file on Catbox
+///
+/// Returns URLs unchanged, adds the scheme to links without one,
+/// and treats everything else as the ID of a file on Catbox
+///
+/// # Arguments
+///
+/// * `file` - URL or ID of the file, e.g. `abc123.png`
+pub fn url(file: &str) -> String {
+    if Url::parse(file).is_ok() {
+        file.to_string()
+    } else {
+        file.parse::<Link>().map_or_else(|_| file_url(file), |link| link.url())
+    }
+}
+
+/// Download a file into a directory
+///
+/// Links returned by encrypted uploads carry the key in their fragment.
+/// Such files are decrypted while downloading and saved without the `.enc` extension.
+///
+/// Returns the path of the downloaded file
+///
+/// # Arguments
+///
+/// * `url` - Link to the file
+/// * `dir` - Directory to save the file in
+pub async fn file<S: Into<String>>(url: S, dir: S) -> Result<String> {
+    Ok(file_with(url, dir, &DownloadOptions::default()).await?.path)
+}
+
+/// Download a file into a directory after applying the given options
+///
+/// # Arguments
+///
+/// * `url` - Link to the file
+/// * `dir` - Directory to save the file in
+/// * `options` - Transformations applied to the file while it is downloaded
+pub async fn file_with<S: Into<String>>(
+    url: S,
+    dir: S,
+    options: &DownloadOptions
+) -> Result<Downloaded> {
+    let mut url = Url::parse(&url.into())?;
+
+    let key = url.fragment().map(Key::from_fragment).transpose()?;
+    url.set_fragment(None);
+
+    let mut name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("{url} does not point to a file"))?
+        .to_string();
+
+    if key.is_some() && let Some(stripped) = name.strip_suffix(&format!(".{}", crypt::EXTENSION)) {
+        name = stripped.to_string();
+    }
+
+    let compression = Compression::from_file_name(&name).filter(|_| options.decompress);
+
+    if let Some(compression) = compression {
+        name.truncate(name.len() - compression.extension().len() - 1);
+    }
+
+    let dir = dir.into();
+    let path = Path::new(&dir).join(options.name.as_ref().unwrap_or(&name));
+    let decoded = key.is_some() || compression.is_some();
+
+    if !options.overwrite && fs::try_exists(&path).await? {
+        return Ok(Downloaded { path: display(&path), skipped: true, resumed: 0, decoded });
+    }
+
+    fs::create_dir_all(&dir).await?;
+
+    let part = part_path(&path);
+    let resumed = fetch(url, &part).await?;
+
+    if decoded {
+        decode(&part, &path, key.as_ref(), compression).await?;
+        fs::remove_file(&part).await?;
+    } else {
+        fs::rename(&part, &path).await?;
+    }
+
+    Ok(Downloaded { path: display(&path), skipped: false, resumed, decoded })
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Fetch information about a remote file and its first bytes, without downloading all of it
+///
+/// # Arguments
+///
+/// * `url` - Link to the file
+/// * `bytes` - Number of bytes to fetch from the beginning of the file, at least 1
+pub async fn peek<S: Into<String>>(url: S, bytes: u64) -> Result<Peek> {
+    let response = Client::builder()
+        .user_agent(UASTRING)
+        .build()
+        .unwrap_or_else(|_| Client::new())
+        .get(url.into())
+        .header(RANGE, format!("bytes=0-{}", bytes.max(1) - 1))
+        .send().await?
+        .error_for_status()?;
+
+    let header = |name| {
+        response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+    };
+
+    // servers ignoring the range send the whole file
+    let size = if response.status() == StatusCode::PARTIAL_CONTENT {
+        header(CONTENT_RANGE).and_then(|range| range.rsplit('/').next()?.parse().ok())
+    } else {
+        header(CONTENT_LENGTH).and_then(|length| length.parse().ok())
+    };
+
+    let modified = header(LAST_MODIFIED).and_then(|date| http_date(&date));
+
+    let mut head = Vec::new();
+    let mut response = response;
+
+    while let Some(chunk) = response.chunk().await? {
+        head.extend_from_slice(&chunk);
+
+        if head.len() as u64 >= bytes {
+            break;
+        }
+    }
+
+    head.truncate(usize::try_from(bytes).unwrap_or(usize::MAX));
+
+    Ok(Peek { size, modified, head })
+}
+
+/// Check whether a remote file still exists
+///
+/// Only a `404 Not Found` response means the file is gone, any other error is returned.
+///
+/// # Arguments
+///
+/// * `url` - Link to the file
+pub async fn exists<S: Into<String>>(url: S) -> Result<bool> {
+    match peek(url, 1).await {
+        Ok(_) => Ok(true),
+        Err(err) if status(&err) == Some(StatusCode::NOT_FOUND) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn status(err: &anyhow::Error) -> Option<StatusCode> {
+    err.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status)
+}
+
+/// Parse a date like `Sun, 06 Nov 1994 08:49:37 GMT` into a Unix timestamp
+fn http_date(date: &str) -> Option<u64> {
+    let parts: Vec<_> = date.split_whitespace().collect();
+
+    let [_, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+
+    let month = (MONTHS.iter().position(|name| name == month)? as u64) + 1;
+    let day: u64 = day.parse().ok()?;
+    let year: u64 = year.parse().ok()?;
+
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+
+    let [hours, minutes, seconds] = time.as_slice() else {
+        return None;
+    };
+
+    let valid = (1..=31).contains(&day) && *hours < 24 && *minutes < 60 && *seconds < 60;
+
+    if year < 1970 || !valid {
+        return None;
+    }
+
+    // days since 1970-01-01, counting years from March so leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
+/// Download into `part`, resuming from its current length
+///
+/// Returns the number of bytes which were already present
+async fn fetch(url: Url, part: &Path) -> Result<u64> {
+    let existing = fs::metadata(part).await.map_or(0, |metadata| metadata.len());
+
+    let mut request = Client::builder()
+        .user_agent(UASTRING)
+        .build()
+        .unwrap_or_else(|_| Client::new())
+        .get(url);
+
+    if existing > 0 {
+        request = request.header(RANGE, format!("bytes={existing}-"));
+    }
+
+    let mut response = request.send().await?;
+
+    let resumed = match response.status() {
+        StatusCode::PARTIAL_CONTENT => existing,
+        // the part file already contains the whole file
+        StatusCode::RANGE_NOT_SATISFIABLE if existing > 0 => {
+            return Ok(existing);
+        }
+        _ => {
+            response = response.error_for_status()?;
+            0
+        }
+    };
+
+    let mut output = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed > 0)
+        .truncate(resumed == 0)
+        .open(part).await?;
+
+    while let Some(chunk) = response.chunk().await? {
+        output.write_all(&chunk).await?;
+    }
+
+    output.flush().await?;
+
+    Ok(resumed)
+}
+
+/// Decrypt and/or decompress a completely downloaded file
+async fn decode(
+    part: &Path,
+    path: &Path,
+    key: Option<&Key>,
+    compression: Option<Compression>
+) -> Result<()> {
+    let file = File::create(path).await?;
+
+    let mut output: Box<dyn AsyncWrite + Unpin + Send> = match compression {
+        Some(Compression::Zstd) => Box::new(ZstdDecoder::new(file)),
+        Some(Compression::Gzip) => Box::new(GzipDecoder::new(file)),
+        None => Box::new(file),
+    };
+
+    let mut input = File::open(part).await?;
+    let mut decryptor = key.map(Decryptor::new);
+    let mut buffer = vec![0; crypt::CHUNK_SIZE];
+
+    let result = async {
+        loop {
+            let read = input.read(&mut buffer).await?;
+
+            if read == 0 {
+                break;
+            }
+
+            match &mut decryptor {
+                Some(decryptor) => output.write_all(&decryptor.update(&buffer[..read])?).await?,
+                None => output.write_all(&buffer[..read]).await?,
+            }
+        }
+
+        if let Some(decryptor) = decryptor {
+            output.write_all(&decryptor.finish()?).await?;
+        }
+
+        output.shutdown().await?;
+
+        Ok::<_, anyhow::Error>(())
+    }.await;
+
+    if let Err(err) = result {
+        // keep the downloaded part, but don't leave a half decoded file behind
+        let _ = fs::remove_file(path).await;
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::{ UploadOptions, prepare };
+    use http_body_util::BodyExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parse_http_date() {
+        assert_eq!(http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(http_date("Tue, 29 Feb 2000 23:59:59 GMT"), Some(951_868_799));
+
+        for invalid in [
+            "Thu, 00 Jan 1970 00:00:00 GMT",
+            "Thu, 32 Jan 1970 00:00:00 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:37 GMT",
+            "Sun, 06 Nov 1994 08:49:60 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Nob 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(http_date(invalid), None, "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn decompress() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("app.log");
+        let data = "GET /index.html 200\n".repeat(10_000);
+        fs::write(&path, &data).await?;
+
+        for compression in [Compression::Zstd, Compression::Gzip] {
+            let options = UploadOptions { compress: Some(compression), ..Default::default() };
+            let prepared = prepare(&path.to_string_lossy(), &options).await?;
+
+            assert_eq!(prepared.file_name, format!("app.log.{}", compression.extension()));
+
+            let compressed = prepared.body.collect().await?.to_bytes();
+            assert!(compressed.len() < data.len() / 10);
+
+            let part = dir.path().join(&prepared.file_name);
+            let decoded = dir.path().join("decoded.log");
+            fs::write(&part, &compressed).await?;
+
+            decode(&part, &decoded, None, Some(compression)).await?;
+            assert_eq!(fs::read_to_string(&decoded).await?, data);
+
+            // a corrupted download leaves no half decoded file behind
+            fs::write(&part, &compressed[..compressed.len() / 2]).await?;
+            assert!(decode(&part, &decoded, None, Some(compression)).await.is_err());
+            assert!(!fs::try_exists(&decoded).await?);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Dry runs, which print requests instead of sending them
+//!
+//! While a dry run is enabled, the functions in `file`, `album` and `litter` which change
+//! anything on the server print the request they would send and return a placeholder response.
+//! Reading public pages, e.g. with [`crate::album::show`], still contacts the server.
+
+use std::{ path::Path, sync::atomic::{ AtomicBool, Ordering } };
+
+use crate::url::{ album_url, file_url, litter_url };
+
+/// ID used in the placeholder responses for uploads and created albums
+pub const PLACEHOLDER_ID: &str = "dryrun";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable dry runs for the whole process
+///
+/// # Arguments
+///
+/// * `enabled` - Whether requests should only be printed
+pub fn set(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether requests are only printed
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Describe a request, with the user hash redacted
+///
+/// # Arguments
+///
+/// * `url` - API endpoint the request is sent to
+/// * `fields` - Names and values of the text fields, in the order they are sent
+/// * `file` - Name and size (if known) of the uploaded file
+pub fn describe(url: &str, fields: &[(&str, &str)], file: Option<(&str, Option<u64>)>) -> String {
+    let mut lines = vec![format!("Dry run: POST {url}")];
+
+    for (name, value) in fields {
+        let value = if *name == "userhash" && !value.is_empty() { "<redacted>" } else { value };
+        lines.push(format!("  {name}: {value}"));
+    }
+
+    if let Some((file_name, size)) = file {
+        lines.push(
+            match size {
+                Some(size) => format!("  fileToUpload: {file_name} ({size} bytes)"),
+                None => format!("  fileToUpload: {file_name} (size unknown until sent)"),
+            }
+        );
+    }
+
+    lines.join("\n")
+}
+
+/// Print a request which is not sent
+pub(crate) fn report(url: &str, fields: &[(&str, &str)], file: Option<(&str, Option<u64>)>) {
+    println!("{}", describe(url, fields, file));
+}
+
+/// Placeholder link for an upload, keeping the extension of the file
+pub(crate) fn file_link(file_name: &str) -> String {
+    file_url(placeholder_name(file_name))
+}
+
+/// Placeholder link for a Litterbox upload, keeping the extension of the file
+pub(crate) fn litter_link(file_name: &str) -> String {
+    litter_url(placeholder_name(file_name))
+}
+
+/// Placeholder link for a created album
+pub(crate) fn album_link() -> String {
+    album_url(PLACEHOLDER_ID)
+}
+
+fn placeholder_name(file_name: &str) -> String {
+    match Path::new(file_name).extension() {
+        Some(extension) => format!("{PLACEHOLDER_ID}.{}", extension.to_string_lossy()),
+        None => PLACEHOLDER_ID.to_string(),
+    }
+}
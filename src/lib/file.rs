@@ -6,11 +6,23 @@
 //!
 //! See <https://catbox.moe/faq.php> for allowed filetypes and content.
 
-use anyhow::Result;
+use anyhow::{ Result, bail };
 use reqwest::{ Client, multipart::{ Form, Part } };
 
 #[allow(clippy::wildcard_imports)]
-use crate::{ CATBOX_API_URL, UASTRING, helper::* };
+use crate::{
+    CATBOX_API_URL,
+    UASTRING,
+    crypt,
+    dry_run,
+    helper::*,
+    id::{ self, FileId, UserHash },
+    upload::{ Prepared, UploadOptions, link, prepare },
+    url,
+};
+
+/// Response of Catbox to a successful deletion
+pub const DELETED: &str = "Files successfully deleted.";
 
 /// Upload a file to catbox.
 ///
@@ -22,14 +34,25 @@ use crate::{ CATBOX_API_URL, UASTRING, helper::* };
 ///
 /// * `file_path` - Path to the file to be uploaded
 /// * `user_hash` - User account hash, required for deletions. Otherwise optional.
-pub async fn from_file<S: Into<String>>(file_path: S, user_hash: S) -> Result<String> {
+pub async fn from_file<S: Into<String>>(
+    file_path: S,
+    user_hash: Option<&UserHash>
+) -> Result<String> {
     let file_path = file_path.into();
     let file = file_stream(&file_path).await?;
     let file_name = file_name(&file_path);
 
+    if dry_run::enabled() {
+        let size = tokio::fs::metadata(&file_path).await?.len();
+        let fields = [("reqtype", "fileupload"), ("userhash", id::user_hash(user_hash))];
+
+        dry_run::report(CATBOX_API_URL, &fields, Some((&file_name, Some(size))));
+        return Ok(dry_run::file_link(&file_name));
+    }
+
     let form = Form::new()
         .text("reqtype", "fileupload")
-        .text("userhash", user_hash.into())
+        .text("userhash", id::user_hash(user_hash).to_string())
         .part("fileToUpload", Part::stream(file).file_name(file_name));
 
     Ok(
@@ -44,6 +67,116 @@ pub async fn from_file<S: Into<String>>(file_path: S, user_hash: S) -> Result<St
     )
 }
 
+/// Upload a file to catbox after applying the given options.
+///
+/// Returns an URL to the file. For encrypted uploads the key is appended as the URL fragment.
+///
+/// See <https://catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file to be uploaded
+/// * `user_hash` - User account hash, required for deletions. Otherwise optional.
+/// * `options` - Transformations applied to the file before it is uploaded
+pub async fn from_file_with<S: Into<String>>(
+    file_path: S,
+    user_hash: Option<&UserHash>,
+    options: &UploadOptions
+) -> Result<String> {
+    upload_prepared(prepare(&file_path.into(), options).await?, user_hash).await
+}
+
+/// Upload a file prepared with [`crate::upload::prepare`] to catbox.
+///
+/// Returns an URL to the file. For encrypted uploads the key is appended as the URL fragment.
+///
+/// # Arguments
+///
+/// * `prepared` - File contents and name to be uploaded
+/// * `user_hash` - User account hash, required for deletions. Otherwise optional.
+pub async fn upload_prepared(prepared: Prepared, user_hash: Option<&UserHash>) -> Result<String> {
+    if dry_run::enabled() {
+        let fields = [("reqtype", "fileupload"), ("userhash", id::user_hash(user_hash))];
+        let file_name = prepared.file_name.as_str();
+
+        dry_run::report(CATBOX_API_URL, &fields, Some((file_name, prepared.size)));
+        return Ok(link(prepared.key.as_ref(), dry_run::file_link(file_name)));
+    }
+
+    let mut part = Part::stream(prepared.body).file_name(prepared.file_name);
+
+    if let Some(mime) = &prepared.mime {
+        part = part.mime_str(mime)?;
+    }
+
+    let form = Form::new()
+        .text("reqtype", "fileupload")
+        .text("userhash", id::user_hash(user_hash).to_string())
+        .part("fileToUpload", part);
+
+    Ok(
+        link(
+            prepared.key.as_ref(),
+            Client::builder()
+                .user_agent(UASTRING)
+                .build()
+                .unwrap_or_else(|_| Client::new())
+                .post(CATBOX_API_URL)
+                .multipart(form)
+                .send().await?
+                .text().await?
+        )
+    )
+}
+
+/// Upload a page which decrypts an encrypted upload in the browser
+///
+/// Returns an URL to the page, with the key of the encrypted file as its fragment
+///
+/// # Arguments
+///
+/// * `link` - Link returned by an encrypted upload, including the key
+/// * `file_name` - Name the decrypted file is saved as
+/// * `user_hash` - User account hash, required for deletions. Otherwise optional.
+pub async fn share_page<S: Into<String>>(
+    link: S,
+    file_name: S,
+    user_hash: Option<&UserHash>
+) -> Result<String> {
+    let link = link.into();
+    let file_name = file_name.into();
+
+    let Some((file_url, key)) = link.split_once('#') else {
+        bail!("{link} does not contain an encryption key");
+    };
+
+    let page = crypt::share_page(file_url, &file_name);
+
+    if dry_run::enabled() {
+        let fields = [("reqtype", "fileupload"), ("userhash", id::user_hash(user_hash))];
+        let page_name = format!("{file_name}.html");
+
+        dry_run::report(CATBOX_API_URL, &fields, Some((&page_name, Some(page.len() as u64))));
+        return Ok(format!("{}#{key}", dry_run::file_link(&page_name)));
+    }
+
+    let form = Form::new()
+        .text("reqtype", "fileupload")
+        .text("userhash", id::user_hash(user_hash).to_string())
+        .part("fileToUpload", Part::text(page).file_name(format!("{file_name}.html")));
+
+    let response = Client::builder()
+        .user_agent(UASTRING)
+        .build()
+        .unwrap_or_else(|_| Client::new())
+        .post(CATBOX_API_URL)
+        .multipart(form)
+        .send().await?
+        .text().await?;
+
+    Ok(if response.starts_with("http") { format!("{}#{key}", response.trim()) } else { response })
+}
+
 /// Upload contents from a URL to catbox
 ///
 /// Returns n URL to the file
@@ -54,13 +187,18 @@ pub async fn from_file<S: Into<String>>(file_path: S, user_hash: S) -> Result<St
 ///
 /// * `url` - URL to file
 /// * `user_hash` - User account hash, required for deletions. Otherwise optional.
-pub async fn from_url<S: Into<String>>(url: S, user_hash: S) -> Result<String> {
+pub async fn from_url<S: Into<String>>(url: S, user_hash: Option<&UserHash>) -> Result<String> {
     let form = [
         ("reqtype", "urlupload"),
-        ("userhash", &user_hash.into()),
+        ("userhash", id::user_hash(user_hash)),
         ("url", &url.into()),
     ];
 
+    if dry_run::enabled() {
+        dry_run::report(CATBOX_API_URL, &form, None);
+        return Ok(url::file_url(dry_run::PLACEHOLDER_ID));
+    }
+
     Ok(
         Client::builder()
             .user_agent(UASTRING)
@@ -75,21 +213,24 @@ pub async fn from_url<S: Into<String>>(url: S, user_hash: S) -> Result<String> {
 
 /// Delete files
 ///
-/// Returns "Files successfully deleted." on success
+/// Returns [`DELETED`] on success, or an error message from Catbox
 ///
 /// # Arguments
 ///
-/// * `files` - Names of the files to be deleted
-/// * `user_hash` - User account hash, required for deletions. Otherwise optional.
-pub async fn delete<S: Into<String>>(files: Vec<S>, user_hash: S) -> Result<String> {
-    let files: Vec<_> = files.into_iter().map(Into::into).collect();
-
+/// * `files` - IDs of the files to be deleted
+/// * `user_hash` - User account hash, required for deletions.
+pub async fn delete(files: &[FileId], user_hash: &UserHash) -> Result<String> {
     let form = [
         ("reqtype", "deletefiles"),
-        ("userhash", &user_hash.into()),
-        ("files", &files.join(" ")),
+        ("userhash", user_hash.as_str()),
+        ("files", &id::join(files)),
     ];
 
+    if dry_run::enabled() {
+        dry_run::report(CATBOX_API_URL, &form, None);
+        return Ok(DELETED.to_string());
+    }
+
     Ok(
         Client::builder()
             .user_agent(UASTRING)
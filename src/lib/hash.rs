@@ -0,0 +1,61 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! SHA-256 hashes of file contents
+
+use anyhow::Result;
+use sha2::{ Digest, Sha256 };
+use std::fmt::Write;
+use tokio::{ fs::File, io::{ AsyncRead, AsyncReadExt } };
+
+/// Hash a byte slice
+///
+/// Returns the lowercase hex encoded SHA-256 hash
+///
+/// # Arguments
+///
+/// * `data` - Data to hash
+pub fn bytes(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// Hash everything read from a reader
+///
+/// Returns the lowercase hex encoded SHA-256 hash
+///
+/// # Arguments
+///
+/// * `reader` - Source of the data to hash
+pub async fn reader<R: AsyncRead + Unpin>(reader: &mut R) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Hash a file
+///
+/// Returns the lowercase hex encoded SHA-256 hash
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file
+pub async fn file(file_path: &str) -> Result<String> {
+    reader(&mut File::open(file_path).await?).await
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().fold(String::with_capacity(64), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
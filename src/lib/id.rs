@@ -0,0 +1,130 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Validated IDs of files and albums, and user hashes
+//!
+//! Parsing these catches typos before a request is sent.
+//! Use [`crate::url`] to get IDs from links.
+
+use anyhow::{ Result, bail };
+use serde::{ Deserialize, Serialize };
+use std::{ fmt, str::FromStr };
+
+const MAX_FILE_ID_LEN: usize = 255;
+const MAX_ALBUM_SHORT_LEN: usize = 32;
+const MAX_USER_HASH_LEN: usize = 64;
+
+/// Implement the conversions shared by all ID types
+macro_rules! id_type {
+    ($name:ident, $validate:ident) => {
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> Result<Self> {
+                $validate(s)?;
+                Ok(Self(s.to_string()))
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = anyhow::Error;
+
+            fn try_from(s: String) -> Result<Self> {
+                $validate(&s)?;
+                Ok(Self(s))
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    };
+}
+
+/// ID of a file on Catbox or Litterbox, e.g. `abc123.png`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FileId(String);
+
+id_type!(FileId, validate_file_id);
+
+fn validate_file_id(s: &str) -> Result<()> {
+    let valid_chars = s.chars().all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+
+    if s.is_empty() || s.len() > MAX_FILE_ID_LEN || !valid_chars || s.starts_with('.') {
+        bail!("{s:?} is not a valid file ID (e.g. abc123.png)");
+    }
+
+    Ok(())
+}
+
+/// ID of an album, e.g. `abc123` for `https://catbox.moe/c/abc123`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AlbumShort(String);
+
+id_type!(AlbumShort, validate_album_short);
+
+fn validate_album_short(s: &str) -> Result<()> {
+    let valid_chars = s.chars().all(|c| c.is_ascii_alphanumeric());
+
+    if s.is_empty() || s.len() > MAX_ALBUM_SHORT_LEN || !valid_chars {
+        bail!("{s:?} is not a valid album ID (e.g. abc123)");
+    }
+
+    Ok(())
+}
+
+/// Hash identifying a Catbox account
+///
+/// The hash is redacted when printed with `Debug`, use `Display` to get the actual value.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UserHash(String);
+
+id_type!(UserHash, validate_user_hash);
+
+fn validate_user_hash(s: &str) -> Result<()> {
+    let valid_chars = s.chars().all(|c| c.is_ascii_alphanumeric());
+
+    if s.is_empty() || s.len() > MAX_USER_HASH_LEN || !valid_chars {
+        bail!("The user hash must consist of up to {MAX_USER_HASH_LEN} letters and digits");
+    }
+
+    Ok(())
+}
+
+impl fmt::Debug for UserHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UserHash(<redacted>)")
+    }
+}
+
+/// Join IDs the way the API expects them
+pub(crate) fn join<I: AsRef<str>>(ids: &[I]) -> String {
+    ids.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(" ")
+}
+
+/// Form value for an optional user hash, anonymous requests send an empty hash
+pub(crate) fn user_hash(user_hash: Option<&UserHash>) -> &str {
+    user_hash.map_or("", UserHash::as_str)
+}
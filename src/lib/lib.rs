@@ -2,10 +2,16 @@
 
 //! Unofficial library implementing catbox.moe's API in Rust
 //!
-//! Separates funtionalities into three modules:
+//! Separates funtionalities into these modules:
 //! * `file` for uploading and deleting singular files
 //! * `album` for album operations with existing files on Catbox
 //! * `litter` for uploading temporary files to Litterbox
+//! * `download` for fetching files from Catbox and Litterbox
+//! * `upload`, `crypt`, `metadata`, `resize`, `sniff` and `naming` for preparing uploads
+//! * `hash` for hashing file contents
+//! * `url` for parsing and building links to files and albums
+//! * `id` for validated file IDs, album IDs and user hashes
+//! * `dry_run` for printing requests instead of sending them
 //!
 //! See <https://catbox.moe/faq.php> for allowed filetypes and content,
 //! as well as other questions.
@@ -15,8 +21,19 @@
 mod helper;
 
 pub mod album;
+pub mod crypt;
+pub mod download;
+pub mod dry_run;
 pub mod file;
+pub mod hash;
+pub mod id;
 pub mod litter;
+pub mod metadata;
+pub mod naming;
+pub mod resize;
+pub mod sniff;
+pub mod upload;
+pub mod url;
 
 static CATBOX_API_URL: &str = "https://catbox.moe/user/api.php";
 static LITTER_API_URL: &str = "https://litterbox.catbox.moe/resources/internals/api.php";
@@ -9,7 +9,13 @@
 use anyhow::Result;
 use reqwest::{ Client, multipart::{ Form, Part } };
 
-use crate::{ LITTER_API_URL, UASTRING, helper::{ file_name, file_stream } };
+use crate::{
+    LITTER_API_URL,
+    UASTRING,
+    dry_run,
+    helper::{ file_name, file_stream },
+    upload::{ Prepared, UploadOptions, link, prepare },
+};
 
 /// Upload a temporary file to litterbox.
 /// Max size 1GB.
@@ -25,6 +31,14 @@ pub async fn upload<S: Into<String>>(file_path: S, time: u8) -> Result<String> {
     let file = file_stream(&file_path).await?;
     let file_name = file_name(&file_path);
 
+    if dry_run::enabled() {
+        let size = tokio::fs::metadata(&file_path).await?.len();
+        let fields = [("reqtype", "fileupload"), ("time", &format!("{time}h"))];
+
+        dry_run::report(LITTER_API_URL, &fields, Some((&file_name, Some(size))));
+        return Ok(dry_run::litter_link(&file_name));
+    }
+
     let form = Form::new()
         .text("reqtype", "fileupload")
         .text("time", format!("{time}h"))
@@ -41,3 +55,67 @@ pub async fn upload<S: Into<String>>(file_path: S, time: u8) -> Result<String> {
             .text().await?
     )
 }
+
+/// Upload a temporary file to litterbox after applying the given options.
+/// Max size 1GB.
+///
+/// For encrypted uploads the key is appended as the URL fragment.
+///
+/// See <https://litterbox.catbox.moe/faq.php> for allowed formats and content.
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file to be uploaded
+/// * `time` - Time until expiration. Valid values are 1, 12, 24 and 72.
+/// * `options` - Transformations applied to the file before it is uploaded
+pub async fn upload_with<S: Into<String>>(
+    file_path: S,
+    time: u8,
+    options: &UploadOptions
+) -> Result<String> {
+    upload_prepared(prepare(&file_path.into(), options).await?, time).await
+}
+
+/// Upload a file prepared with [`crate::upload::prepare`] to litterbox.
+/// Max size 1GB.
+///
+/// For encrypted uploads the key is appended as the URL fragment.
+///
+/// # Arguments
+///
+/// * `prepared` - File contents and name to be uploaded
+/// * `time` - Time until expiration. Valid values are 1, 12, 24 and 72.
+pub async fn upload_prepared(prepared: Prepared, time: u8) -> Result<String> {
+    if dry_run::enabled() {
+        let fields = [("reqtype", "fileupload"), ("time", &format!("{time}h"))];
+        let file_name = prepared.file_name.as_str();
+
+        dry_run::report(LITTER_API_URL, &fields, Some((file_name, prepared.size)));
+        return Ok(link(prepared.key.as_ref(), dry_run::litter_link(file_name)));
+    }
+
+    let mut part = Part::stream(prepared.body).file_name(prepared.file_name);
+
+    if let Some(mime) = &prepared.mime {
+        part = part.mime_str(mime)?;
+    }
+
+    let form = Form::new()
+        .text("reqtype", "fileupload")
+        .text("time", format!("{time}h"))
+        .part("fileToUpload", part);
+
+    Ok(
+        link(
+            prepared.key.as_ref(),
+            Client::builder()
+                .user_agent(UASTRING)
+                .build()
+                .unwrap_or_else(|_| Client::new())
+                .post(LITTER_API_URL)
+                .multipart(form)
+                .send().await?
+                .text().await?
+        )
+    )
+}
@@ -0,0 +1,557 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Removal of EXIF, GPS, XMP and other metadata from images, and reading of capture dates
+//!
+//! Metadata is removed on the container level, the image data itself is left untouched.
+//! Colour profiles and the EXIF orientation of JPEGs are kept, since removing them changes
+//! how images are displayed.
+
+use anyhow::{ Result, bail };
+
+/// Image formats metadata can be stripped from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    WebP,
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+/// Detect the image format from the first bytes of a file
+///
+/// # Arguments
+///
+/// * `header` - At least the first 12 bytes of the file
+pub fn detect(header: &[u8]) -> Option<ImageFormat> {
+    if header.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some(ImageFormat::Jpeg)
+    } else if header.starts_with(PNG_SIGNATURE) {
+        Some(ImageFormat::Png)
+    } else if header.len() >= 12 && &header[..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        Some(ImageFormat::WebP)
+    } else {
+        None
+    }
+}
+
+/// Remove metadata from an image
+///
+/// Returns `None` if the data is not a supported image
+///
+/// # Arguments
+///
+/// * `data` - Contents of the image file
+pub fn strip(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    Ok(
+        match detect(data) {
+            Some(ImageFormat::Jpeg) => Some(strip_jpeg(data)?),
+            Some(ImageFormat::Png) => Some(strip_png(data)?),
+            Some(ImageFormat::WebP) => Some(strip_webp(data)?),
+            None => None,
+        }
+    )
+}
+
+/// Drop APP1 (EXIF, XMP), APP3 to APP13 (IPTC, ...), APP15, comments and MPF indexes.
+/// APP0 (JFIF), APP2 (ICC profile) and APP14 (Adobe colour transform) are kept.
+/// EXIF data is replaced with a minimal block holding only the orientation, if it is not upright.
+///
+/// The output ends with the primary image, so images embedded after it
+/// (MPF pictures, thumbnails of phone trailers) are dropped with their metadata.
+fn strip_jpeg(data: &[u8]) -> Result<Vec<u8>> {
+    let mut output = data[..2].to_vec();
+    let mut pos = 2;
+
+    while pos < data.len() {
+        if data[pos] != 0xff {
+            bail!("Invalid JPEG segment at byte {pos}");
+        }
+
+        let marker = data.get(pos + 1).copied().unwrap_or_default();
+
+        match marker {
+            // fill byte
+            0xff => {
+                pos += 1;
+                continue;
+            }
+            // markers without a length
+            0x01 | 0xd0..=0xd7 => {
+                output.extend_from_slice(&data[pos..pos + 2]);
+                pos += 2;
+                continue;
+            }
+            // end of the primary image
+            0xd9 => {
+                output.extend_from_slice(&data[pos..pos + 2]);
+                break;
+            }
+            _ => {}
+        }
+
+        let Some(length) = data.get(pos + 2..pos + 4) else {
+            bail!("Truncated JPEG segment at byte {pos}");
+        };
+        let end = pos + 2 + usize::from(u16::from_be_bytes([length[0], length[1]]));
+
+        if end > data.len() {
+            bail!("Truncated JPEG segment at byte {pos}");
+        }
+
+        let payload = &data[(pos + 4).min(end)..end];
+
+        match marker {
+            // start of scan, followed by image data up to the next marker
+            0xda => {
+                let scan_end = scan_end(data, end);
+                output.extend_from_slice(&data[pos..scan_end]);
+                pos = scan_end;
+                continue;
+            }
+            // everything but the orientation, so rotated photos are still shown upright
+            0xe1 if payload.starts_with(EXIF_HEADER) => {
+                if let Some(orientation) = tiff_orientation(&payload[EXIF_HEADER.len()..]) {
+                    output.extend_from_slice(&orientation_segment(orientation));
+                }
+            }
+            0xe2 if payload.starts_with(b"MPF\0") => {}
+            0xe1 | 0xe3..=0xed | 0xef | 0xfe => {}
+            _ => output.extend_from_slice(&data[pos..end]),
+        }
+
+        pos = end;
+    }
+
+    Ok(output)
+}
+
+/// Position of the first marker after the image data of a scan
+///
+/// Stuffed bytes (`0xff 0x00`) and restart markers are part of the image data.
+fn scan_end(data: &[u8], start: usize) -> usize {
+    (start..data.len().saturating_sub(1))
+        .find(|&pos| data[pos] == 0xff && !matches!(data[pos + 1], 0x00 | 0xd0..=0xd7))
+        .unwrap_or(data.len())
+}
+
+/// Drop `eXIf`, text and timestamp chunks.
+fn strip_png(data: &[u8]) -> Result<Vec<u8>> {
+    let mut output = PNG_SIGNATURE.to_vec();
+    let mut pos = PNG_SIGNATURE.len();
+
+    while pos < data.len() {
+        let Some(header) = data.get(pos..pos + 8) else {
+            bail!("Truncated PNG chunk at byte {pos}");
+        };
+
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let end = pos + 12 + usize::try_from(length)?;
+
+        if end > data.len() {
+            bail!("Truncated PNG chunk at byte {pos}");
+        }
+
+        if !matches!(&header[4..8], b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            output.extend_from_slice(&data[pos..end]);
+        }
+
+        pos = end;
+    }
+
+    Ok(output)
+}
+
+/// Drop `EXIF` and `XMP ` chunks and clear their flags in the `VP8X` header.
+fn strip_webp(data: &[u8]) -> Result<Vec<u8>> {
+    const EXIF_FLAG: u8 = 0x08;
+    const XMP_FLAG: u8 = 0x04;
+
+    let mut output = data[..12].to_vec();
+    let mut pos = 12;
+
+    while pos < data.len() {
+        let Some(header) = data.get(pos..pos + 8) else {
+            bail!("Truncated WebP chunk at byte {pos}");
+        };
+
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let size = usize::try_from(size)?;
+        // chunks are padded to an even size
+        let end = (pos + 8 + size + (size & 1)).min(data.len());
+
+        if pos + 8 + size > data.len() {
+            bail!("Truncated WebP chunk at byte {pos}");
+        }
+
+        match &header[..4] {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" if size > 0 => {
+                let start = output.len();
+                output.extend_from_slice(&data[pos..end]);
+                output[start + 8] &= !(EXIF_FLAG | XMP_FLAG);
+            }
+            _ => output.extend_from_slice(&data[pos..end]),
+        }
+
+        pos = end;
+    }
+
+    let riff_size = u32::try_from(output.len() - 8)?;
+    output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    Ok(output)
+}
+
+/// Read the date an image was taken from its EXIF data
+///
+/// Returns the date as `YYYY:MM:DD HH:MM:SS`, which sorts chronologically.
+/// Falls back to the modification date if the capture date is missing.
+///
+/// # Arguments
+///
+/// * `data` - Contents of the image file, the beginning is enough if it contains the EXIF data
+pub fn exif_date(data: &[u8]) -> Option<String> {
+    let exif = match detect(data)? {
+        ImageFormat::Jpeg => jpeg_exif(data)?,
+        ImageFormat::Png => png_exif(data)?,
+        ImageFormat::WebP => webp_exif(data)?,
+    };
+
+    tiff_date(exif.strip_prefix(EXIF_HEADER).unwrap_or(exif))
+}
+
+/// Find the APP1 segment with EXIF data, which comes before the image data
+fn jpeg_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+
+    loop {
+        let marker = *data.get(pos + 1)?;
+
+        if marker == 0xda || marker == 0xd9 {
+            return None;
+        }
+
+        let length = data.get(pos + 2..pos + 4)?;
+        let end = pos + 2 + usize::from(u16::from_be_bytes([length[0], length[1]]));
+        let segment = data.get(pos + 4..end)?;
+
+        if marker == 0xe1 && segment.starts_with(EXIF_HEADER) {
+            return Some(segment);
+        }
+
+        pos = end;
+    }
+}
+
+/// Find the `eXIf` chunk
+fn png_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = PNG_SIGNATURE.len();
+
+    loop {
+        let header = data.get(pos..pos + 8)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let end = pos + 8 + usize::try_from(length).ok()?;
+
+        if &header[4..8] == b"eXIf" {
+            return data.get(pos + 8..end);
+        }
+
+        pos = end + 4;
+    }
+}
+
+/// Find the `EXIF` chunk
+fn webp_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 12;
+
+    loop {
+        let header = data.get(pos..pos + 8)?;
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let size = usize::try_from(size).ok()?;
+
+        if &header[..4] == b"EXIF" {
+            return data.get(pos + 8..pos + 8 + size);
+        }
+
+        pos += 8 + size + (size & 1);
+    }
+}
+
+/// Reader for TIFF structured EXIF data
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => {
+                return None;
+            }
+        };
+
+        Some(Self { data, little_endian })
+    }
+
+    fn read_u16(&self, pos: usize) -> Option<u16> {
+        let bytes = self.data.get(pos..pos + 2)?;
+        let bytes = [bytes[0], bytes[1]];
+
+        Some(
+            if self.little_endian {
+                u16::from_le_bytes(bytes)
+            } else {
+                u16::from_be_bytes(bytes)
+            }
+        )
+    }
+
+    fn read_u32(&self, pos: usize) -> Option<usize> {
+        let bytes = self.data.get(pos..pos + 4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        let value = if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        };
+
+        usize::try_from(value).ok()
+    }
+
+    /// Position of the first IFD, which describes the main image
+    fn ifd0(&self) -> Option<usize> {
+        self.read_u32(4)
+    }
+
+    /// Position of the entry for a tag in an IFD
+    ///
+    /// Entries are 12 bytes: tag, type, count and the value or an offset to it.
+    fn find(&self, ifd: usize, tag: u16) -> Option<usize> {
+        (0..self.read_u16(ifd)?)
+            .map(|i| ifd + 2 + usize::from(i) * 12)
+            .find(|&entry| self.read_u16(entry) == Some(tag))
+    }
+}
+
+/// Read the capture or modification date from TIFF structured EXIF data
+fn tiff_date(tiff: &[u8]) -> Option<String> {
+    let tiff = Tiff::new(tiff)?;
+
+    let text = |entry: usize| {
+        let count = tiff.read_u32(entry + 4)?;
+        let offset = if count <= 4 { entry + 8 } else { tiff.read_u32(entry + 8)? };
+        let value = String::from_utf8_lossy(tiff.data.get(offset..offset + count)?);
+        let value = value.trim_end_matches('\0').trim();
+
+        // unknown dates are filled with zeros or spaces
+        value
+            .starts_with(|c: char| c.is_ascii_digit() && c != '0')
+            .then(|| value.to_string())
+    };
+
+    let ifd0 = tiff.ifd0()?;
+
+    let original = tiff
+        .find(ifd0, TAG_EXIF_IFD)
+        .and_then(|entry| tiff.read_u32(entry + 8))
+        .and_then(|exif_ifd| tiff.find(exif_ifd, TAG_DATE_TIME_ORIGINAL))
+        .and_then(text);
+
+    original.or_else(|| tiff.find(ifd0, TAG_DATE_TIME).and_then(text))
+}
+
+/// Read the orientation from TIFF structured EXIF data
+///
+/// Returns `None` for upright images and invalid values
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let tiff = Tiff::new(tiff)?;
+    let entry = tiff.find(tiff.ifd0()?, TAG_ORIENTATION)?;
+
+    // a single SHORT, stored at the start of the value field
+    tiff.read_u16(entry + 8).filter(|orientation| (2..=8).contains(orientation))
+}
+
+/// APP1 segment with EXIF data containing only the orientation
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend_from_slice(&8_u32.to_le_bytes());
+    tiff.extend_from_slice(&1_u16.to_le_bytes());
+    tiff.extend_from_slice(&TAG_ORIENTATION.to_le_bytes());
+    // type SHORT, count 1, value padded to 4 bytes
+    tiff.extend_from_slice(&3_u16.to_le_bytes());
+    tiff.extend_from_slice(&1_u32.to_le_bytes());
+    tiff.extend_from_slice(&u32::from(orientation).to_le_bytes());
+    // no further IFDs
+    tiff.extend_from_slice(&0_u32.to_le_bytes());
+
+    let length = u16::try_from(2 + EXIF_HEADER.len() + tiff.len()).unwrap_or(u16::MAX);
+
+    [&[0xff, 0xe1], &length.to_be_bytes()[..], EXIF_HEADER, &tiff].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOI: &[u8] = &[0xff, 0xd8];
+    const EOI: &[u8] = &[0xff, 0xd9];
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|window| window == needle)
+    }
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let length = u16::try_from(payload.len() + 2).unwrap();
+        [&[0xff, marker], &length.to_be_bytes()[..], payload].concat()
+    }
+
+    fn png_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let length = u32::try_from(data.len()).unwrap();
+        [&length.to_be_bytes()[..], kind, data, &[0; 4]].concat()
+    }
+
+    fn webp_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let size = u32::try_from(data.len()).unwrap();
+        let padding: &[u8] = if data.len() % 2 == 1 { &[0] } else { &[] };
+        [kind, &size.to_le_bytes()[..], data, padding].concat()
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let chunks = chunks.concat();
+        let size = u32::try_from(chunks.len() + 4).unwrap();
+        [b"RIFF", &size.to_le_bytes()[..], b"WEBP", &chunks].concat()
+    }
+
+    #[test]
+    fn strip_jpeg_metadata() -> Result<()> {
+        let app0 = segment(0xe0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        let exif = segment(0xe1, b"Exif\0\0MM\0*GPS 52.5200N 13.4050E");
+        let xmp = segment(0xe1, b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta>Author</x:xmpmeta>");
+        let icc = segment(0xe2, b"ICC_PROFILE\0\x01\x01sRGB");
+        let mpf = segment(0xe2, b"MPF\0MM\0*index");
+        let comment = segment(0xfe, b"Taken at home");
+        let dqt = segment(0xdb, &[0; 65]);
+        let sos = segment(0xda, &[1, 1, 0, 0, 63, 0]);
+        // a stuffed byte and a restart marker are part of the image data
+        let scan: &[u8] = &[0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56];
+
+        let embedded = [SOI, &exif, &sos, scan, EOI].concat();
+
+        let image = [
+            SOI, &app0, &exif, &xmp, &icc, &mpf, &comment, &dqt,
+            // progressive images have several scans
+            &sos, scan, &sos, scan,
+            EOI, &embedded,
+        ].concat();
+
+        let stripped = strip(&image)?.unwrap();
+
+        assert_eq!(stripped, [SOI, &app0, &icc, &dqt, &sos, scan, &sos, scan, EOI].concat());
+
+        for leaked in [&b"GPS"[..], b"xmpmeta", b"Taken at home", b"MPF"] {
+            assert!(!contains(&stripped, leaked));
+        }
+
+        assert!(strip(&image[..image.len() / 3]).is_err());
+
+        // photos taken in portrait keep their orientation, e.g. 6 for rotated by 90°
+        let tiff = [
+            &b"MM\0*"[..],
+            &8_u32.to_be_bytes(),
+            &2_u16.to_be_bytes(),
+            &TAG_ORIENTATION.to_be_bytes(),
+            &[0, 3, 0, 0, 0, 1, 0, 6, 0, 0],
+            &0x8825_u16.to_be_bytes(),
+            &[0, 4, 0, 0, 0, 1, 0, 0, 0, 38],
+            &0_u32.to_be_bytes(),
+            b"GPS 52.5200N 13.4050E",
+        ].concat();
+        let rotated = segment(0xe1, &[EXIF_HEADER, &tiff].concat());
+
+        let image = [SOI, &app0, &rotated, &dqt, &sos, scan, EOI].concat();
+        let stripped = strip(&image)?.unwrap();
+
+        let upright = orientation_segment(6);
+        assert_eq!(stripped, [SOI, &app0, &upright, &dqt, &sos, scan, EOI].concat());
+        assert!(!contains(&stripped, b"GPS"));
+        assert_eq!(tiff_orientation(&upright[4 + EXIF_HEADER.len()..]), Some(6));
+        assert_eq!(tiff_orientation(&orientation_segment(1)[4 + EXIF_HEADER.len()..]), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn strip_png_metadata() -> Result<()> {
+        let ihdr = png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        let iccp = png_chunk(b"iCCP", b"sRGB\0\0profile");
+        let idat = png_chunk(b"IDAT", b"\x78\x9c\x63\x60\x60\x60\x00\x00\x00\x04\x00\x01");
+        let iend = png_chunk(b"IEND", &[]);
+
+        let image = [
+            PNG_SIGNATURE,
+            &ihdr,
+            &png_chunk(b"eXIf", b"MM\0*GPS 52.5200N"),
+            &iccp,
+            &png_chunk(b"tEXt", b"Author\0Jane"),
+            &png_chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x:xmpmeta/>"),
+            &png_chunk(b"tIME", &[7, 234, 10, 19, 12, 0, 0]),
+            &idat,
+            &png_chunk(b"zTXt", b"Comment\0\0compressed"),
+            &iend,
+        ].concat();
+
+        let stripped = strip(&image)?.unwrap();
+
+        assert_eq!(stripped, [PNG_SIGNATURE, &ihdr, &iccp, &idat, &iend].concat());
+
+        for leaked in [&b"GPS"[..], b"Author", b"xmpmeta", b"tIME", b"Comment"] {
+            assert!(!contains(&stripped, leaked));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn strip_webp_metadata() -> Result<()> {
+        const ICC_FLAG: u8 = 0x20;
+
+        let vp8x = |flags| webp_chunk(b"VP8X", &[flags, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let iccp = webp_chunk(b"ICCP", b"profile");
+        let vp8 = webp_chunk(b"VP8 ", b"\x30\x01\x00\x9d\x01\x2a\x01\x00\x01\x00image");
+
+        let image = riff(&[
+            vp8x(ICC_FLAG | 0x08 | 0x04),
+            iccp.clone(),
+            vp8.clone(),
+            webp_chunk(b"EXIF", b"MM\0*GPS 52.5200N"),
+            webp_chunk(b"XMP ", b"<x:xmpmeta>Author</x:xmpmeta>"),
+        ]);
+
+        let stripped = strip(&image)?.unwrap();
+
+        assert_eq!(stripped, riff(&[vp8x(ICC_FLAG), iccp, vp8]));
+
+        for leaked in [&b"GPS"[..], b"xmpmeta"] {
+            assert!(!contains(&stripped, leaked));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn strip_other_files() -> Result<()> {
+        assert_eq!(strip(b"plain text")?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Names files are uploaded with instead of their local names
+//!
+//! Templates may contain these placeholders:
+//! * `{name}` - local file name, including the extension
+//! * `{stem}` - local file name without the extension
+//! * `{ext}` - extension of the file, without the dot
+//! * `{hash}` - SHA-256 hash of the local file, after stripping metadata or resizing but
+//!   before compression and encryption, so it does not match the bytes on Catbox for those
+//! * `{hash8}` - first 8 characters of the hash
+//! * `{random}` - 12 random lowercase letters and digits
+
+use aes_gcm::aead::{ OsRng, rand_core::RngCore };
+use anyhow::{ Result, bail };
+use std::{ path::Path, str::FromStr };
+
+const PLACEHOLDERS: [&str; 6] = ["name", "stem", "ext", "hash", "hash8", "random"];
+const RANDOM_LEN: usize = 12;
+
+/// Name a file is uploaded with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteName {
+    /// Fixed name, used as is
+    Fixed(String),
+    /// Name built from a template
+    Template(Template),
+    /// Random name, keeping the extension
+    Anonymous,
+}
+
+impl RemoteName {
+    /// Whether the hash of the contents is needed to build the name
+    pub fn needs_hash(&self) -> bool {
+        matches!(self, Self::Template(template) if template.0.contains("{hash"))
+    }
+
+    /// Build the name for a file
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Local name of the file
+    /// * `hash` - SHA-256 hash of the file before compression and encryption,
+    ///   required if [`RemoteName::needs_hash`]
+    pub fn render(&self, file_name: &str, hash: Option<&str>) -> Result<String> {
+        match self {
+            Self::Fixed(name) => Ok(name.clone()),
+            Self::Template(template) => template.render(file_name, hash),
+            Self::Anonymous => Template("{random}.{ext}".to_string()).render(file_name, hash),
+        }
+    }
+}
+
+/// Validated name template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(String);
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rest = s;
+
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}') else {
+                bail!("Unclosed placeholder in name template {s}");
+            };
+
+            let placeholder = &rest[start + 1..start + end];
+
+            if !PLACEHOLDERS.contains(&placeholder) {
+                bail!(
+                    "Unknown placeholder {{{placeholder}}} (Options: {})",
+                    PLACEHOLDERS.map(|placeholder| format!("{{{placeholder}}}")).join(", ")
+                );
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        if s.contains(['/', '\\']) {
+            bail!("Name template {s} must not contain path separators");
+        }
+
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl Template {
+    fn render(&self, file_name: &str, hash: Option<&str>) -> Result<String> {
+        let path = Path::new(file_name);
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        let ext = path.extension().map(|ext| ext.to_string_lossy().into_owned());
+
+        let mut name = String::new();
+        let mut rest = self.0.as_str();
+
+        while let Some(start) = rest.find('{') && let Some(end) = rest[start..].find('}') {
+            name.push_str(&rest[..start]);
+
+            let placeholder = &rest[start + 1..start + end];
+
+            let value = match placeholder {
+                "name" => file_name.to_string(),
+                "stem" => stem.clone().unwrap_or_default(),
+                "ext" => ext.clone().unwrap_or_default(),
+                "random" => random(),
+                "hash" | "hash8" => {
+                    let Some(hash) = hash else {
+                        bail!("Name template requires the hash of the file");
+                    };
+
+                    if placeholder == "hash8" { hash[..8].to_string() } else { hash.to_string() }
+                }
+                _ => bail!("Unknown placeholder {{{placeholder}}}"),
+            };
+
+            name.push_str(&value);
+            rest = &rest[start + end + 1..];
+        }
+
+        name.push_str(rest);
+
+        // a template like "{stem}.{ext}" must not leave a trailing dot for files without extension
+        Ok(name.trim_end_matches('.').to_string())
+    }
+}
+
+fn random() -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+    let mut bytes = [0; RANDOM_LEN];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes
+        .iter()
+        .map(|byte| char::from(ALPHABET[usize::from(*byte) % ALPHABET.len()]))
+        .collect()
+}
@@ -0,0 +1,158 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Downscaling and re-encoding of images to fit a size budget
+//!
+//! JPEG images are re-encoded with decreasing quality, PNG and WebP images are converted
+//! to lossless WebP first and to JPEG if they have no transparency. If no encoding fits,
+//! the image is scaled down further and the steps are repeated.
+
+use anyhow::{ Result, bail };
+use image::{
+    DynamicImage,
+    ImageDecoder,
+    ImageFormat,
+    ImageReader,
+    codecs::{ jpeg::JpegEncoder, webp::WebPEncoder },
+    imageops::FilterType,
+};
+use std::io::Cursor;
+
+/// Catbox's maximum file size
+pub const CATBOX_MAX_SIZE: u64 = 200 * 1024 * 1024;
+
+const JPEG_QUALITIES: [u8; 5] = [90, 80, 70, 60, 50];
+const SCALE_STEP: f64 = 0.75;
+const MIN_DIMENSION: u32 = 16;
+
+/// Limits an image has to fit into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Maximum file size in bytes
+    pub max_size: u64,
+    /// Maximum width and height in pixels
+    pub max_dimension: Option<u32>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self { max_size: CATBOX_MAX_SIZE, max_dimension: None }
+    }
+}
+
+/// An image after it has been re-encoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resized {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Extension matching the new format
+    pub extension: &'static str,
+}
+
+/// Downscale and re-encode an image until it fits the budget
+///
+/// Returns `None` if the data is not a supported image, or if it already fits.
+///
+/// # Arguments
+///
+/// * `data` - Contents of the image file
+/// * `budget` - Limits the image has to fit into
+pub fn fit(data: &[u8], budget: &Budget) -> Result<Option<Resized>> {
+    let Ok(format) = image::guess_format(data) else {
+        return Ok(None);
+    };
+
+    if !matches!(format, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP) {
+        return Ok(None);
+    }
+
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+
+    let too_large = budget.max_dimension.is_some_and(|max| image.width().max(image.height()) > max);
+
+    if !too_large && (data.len() as u64) <= budget.max_size {
+        return Ok(None);
+    }
+
+    // pixels are rewritten, so the EXIF orientation has to be applied first
+    image.apply_orientation(orientation);
+
+    if let Some(max) = budget.max_dimension && too_large {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+
+    loop {
+        if let Some(resized) = encode(&image, format, budget.max_size)? {
+            return Ok(Some(resized));
+        }
+
+        let (width, height) = scaled(&image);
+
+        if width.min(height) < MIN_DIMENSION {
+            bail!("Image cannot be re-encoded to fit into {} bytes", budget.max_size);
+        }
+
+        image = image.resize_exact(width, height, FilterType::Lanczos3);
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn scaled(image: &DynamicImage) -> (u32, u32) {
+    (
+        (f64::from(image.width()) * SCALE_STEP) as u32,
+        (f64::from(image.height()) * SCALE_STEP) as u32,
+    )
+}
+
+/// Try all encodings for the current size, returning the first one which fits
+fn encode(image: &DynamicImage, format: ImageFormat, max_size: u64) -> Result<Option<Resized>> {
+    let fits = |data: &Vec<u8>| (data.len() as u64) <= max_size;
+
+    if format != ImageFormat::Jpeg {
+        let webp = encode_webp(image)?;
+
+        if fits(&webp) {
+            return Ok(Some(resized(image, webp, "webp")));
+        }
+
+        if image.color().has_alpha() {
+            return Ok(None);
+        }
+    }
+
+    for quality in JPEG_QUALITIES {
+        let jpeg = encode_jpeg(image, quality)?;
+
+        if fits(&jpeg) {
+            return Ok(Some(resized(image, jpeg, "jpg")));
+        }
+    }
+
+    Ok(None)
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(
+        JpegEncoder::new_with_quality(&mut data, quality)
+    )?;
+    Ok(data)
+}
+
+fn encode_webp(image: &DynamicImage) -> Result<Vec<u8>> {
+    let image = if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    };
+
+    let mut data = Vec::new();
+    image.write_with_encoder(WebPEncoder::new_lossless(&mut data))?;
+    Ok(data)
+}
+
+fn resized(image: &DynamicImage, data: Vec<u8>, extension: &'static str) -> Resized {
+    Resized { data, width: image.width(), height: image.height(), extension }
+}
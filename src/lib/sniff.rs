@@ -0,0 +1,71 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Detection of file types from their contents
+//!
+//! Catbox serves files with a type derived from their extension, so files with a missing
+//! or wrong extension are not rendered by browsers. Only types browsers can display
+//! (images, audio, video and PDF) are detected, to avoid false alarms for container
+//! formats like ZIP based documents.
+
+use infer::MatcherType;
+use std::path::Path;
+
+/// Number of bytes needed to detect a file type
+pub const HEADER_LEN: usize = 8192;
+
+/// Extensions which are used interchangeably
+const ALIASES: &[&[&str]] = &[
+    &["jpg", "jpeg", "jpe", "jfif"],
+    &["tif", "tiff"],
+    &["mp4", "m4v"],
+    &["m4a", "mp4"],
+    &["mov", "qt"],
+    &["mpg", "mpeg"],
+    &["ogg", "oga", "ogv"],
+    &["heic", "heif"],
+    &["mkv", "webm"],
+];
+
+/// File type detected from the contents of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detected {
+    pub extension: &'static str,
+    pub mime: &'static str,
+}
+
+/// Detect the type of a file from its first bytes
+///
+/// Returns `None` for types browsers cannot display, or which cannot be detected.
+///
+/// # Arguments
+///
+/// * `header` - The first [`HEADER_LEN`] bytes of the file
+pub fn detect(header: &[u8]) -> Option<Detected> {
+    let kind = infer::get(header)?;
+
+    let renderable = match kind.matcher_type() {
+        MatcherType::Image | MatcherType::Audio | MatcherType::Video => true,
+        _ => kind.mime_type() == "application/pdf",
+    };
+
+    renderable.then(|| Detected { extension: kind.extension(), mime: kind.mime_type() })
+}
+
+/// Check whether a file name has an extension matching the detected type
+///
+/// # Arguments
+///
+/// * `file_name` - Name of the file
+/// * `detected` - Type detected from the contents of the file
+pub fn matches(file_name: &str, detected: &Detected) -> bool {
+    let Some(extension) = Path::new(file_name).extension() else {
+        return false;
+    };
+
+    let extension = extension.to_string_lossy().to_ascii_lowercase();
+
+    extension == detected.extension ||
+        ALIASES.iter().any(
+            |aliases| aliases.contains(&extension.as_str()) && aliases.contains(&detected.extension)
+        )
+}
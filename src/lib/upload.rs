@@ -0,0 +1,271 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Options for transforming local files before they are uploaded
+
+use anyhow::{ Result, bail };
+use async_compression::tokio::bufread::{ GzipEncoder, ZstdEncoder };
+use reqwest::Body;
+use std::{ fmt, io::{ Cursor, SeekFrom }, path::Path, str::FromStr };
+use tokio::{ fs::File, io::{ AsyncRead, AsyncReadExt, AsyncSeekExt, BufReader } };
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    crypt::{ self, Key },
+    hash,
+    helper::file_name,
+    metadata,
+    naming::RemoteName,
+    resize::{ self, Budget },
+    sniff,
+};
+
+/// Transformations applied to a local file while it is streamed to the server
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UploadOptions {
+    /// Encrypt the file, see [`crate::crypt`]
+    pub encrypt: bool,
+    /// Compress the file, applied before encryption
+    pub compress: Option<Compression>,
+    /// Remove EXIF, GPS and XMP metadata from images, see [`crate::metadata`]
+    pub strip_metadata: bool,
+    /// Downscale and re-encode images to fit a budget, see [`crate::resize`]
+    pub resize: Option<Budget>,
+    /// Replace missing or wrong extensions of images, audio, video and PDFs, see [`crate::sniff`]
+    pub fix_extension: bool,
+    /// Content type sent for the file instead of the detected one
+    pub mime: Option<String>,
+    /// Name the file is uploaded with instead of its local name, see [`crate::naming`]
+    pub remote_name: Option<RemoteName>,
+}
+
+/// Compression formats for uploads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    /// Extension appended to the names of compressed uploads
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Zstd => "zst",
+            Self::Gzip => "gz",
+        }
+    }
+
+    /// Detect the compression format from a file name
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of a compressed file
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        [Self::Zstd, Self::Gzip]
+            .into_iter()
+            .find(|compression| file_name.ends_with(&format!(".{}", compression.extension())))
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "zstd" | "zst" => Ok(Self::Zstd),
+            "gzip" | "gz" => Ok(Self::Gzip),
+            _ => bail!("{s} is not a valid compression (Options: zstd, gzip)"),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Zstd => "zstd",
+            Self::Gzip => "gzip",
+        })
+    }
+}
+
+/// File contents and metadata as they will be sent to the server
+pub struct Prepared {
+    pub body: Body,
+    /// Name of the file sent to the server
+    pub file_name: String,
+    /// Key the contents are encrypted with
+    pub key: Option<Key>,
+    /// Content type sent for the file
+    pub mime: Option<String>,
+    /// Size of the contents sent, unless they are compressed or encrypted while sending
+    pub size: Option<u64>,
+    /// Notes about the transformations which have been applied
+    pub notes: Vec<String>,
+}
+
+/// Append the encryption key (if any) to a link returned by the server
+///
+/// # Arguments
+///
+/// * `key` - Key the upload was encrypted with
+/// * `response` - Server response to the upload
+pub(crate) fn link(key: Option<&Key>, response: String) -> String {
+    match key {
+        Some(key) if response.starts_with("http") =>
+            format!("{}#{}", response.trim(), key.to_fragment()),
+        _ => response,
+    }
+}
+
+/// Open a file and apply the given options to its contents and name
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file
+/// * `options` - Transformations to apply
+pub async fn prepare(file_path: &str, options: &UploadOptions) -> Result<Prepared> {
+    let mut file_name = file_name(file_path);
+    let mut notes = Vec::new();
+
+    let mut file = File::open(file_path).await?;
+
+    let mut header = Vec::new();
+    (&mut file).take(sniff::HEADER_LEN as u64).read_to_end(&mut header).await?;
+    file.seek(SeekFrom::Start(0)).await?;
+
+    let image = (options.strip_metadata || options.resize.is_some()) &&
+        metadata::detect(&header).is_some();
+
+    let data = if image {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await?;
+
+        let data = transform_image(data, options, &mut file_name, &mut notes).await?;
+        header = data[..data.len().min(sniff::HEADER_LEN)].to_vec();
+
+        Some(data)
+    } else {
+        None
+    };
+
+    let detected = sniff::detect(&header);
+
+    if let Some(detected) = detected && !sniff::matches(&file_name, &detected) {
+        if options.fix_extension {
+            let new_name = with_extension(&file_name, detected.extension);
+            notes.push(format!("Renamed {file_name} to {new_name} ({})", detected.mime));
+            file_name = new_name;
+        } else {
+            notes.push(
+                format!(
+                    "Warning: {file_name} looks like {} and may not display in browsers, \
+                    use --fix-extension to rename it to .{}",
+                    detected.mime,
+                    detected.extension
+                )
+            );
+        }
+    }
+
+    if let Some(remote_name) = &options.remote_name {
+        let hash = if !remote_name.needs_hash() {
+            None
+        } else if let Some(data) = &data {
+            Some(hash::bytes(data))
+        } else {
+            let hash = hash::reader(&mut file).await?;
+            file.seek(SeekFrom::Start(0)).await?;
+            Some(hash)
+        };
+
+        file_name = remote_name.render(&file_name, hash.as_deref())?;
+    }
+
+    let mime = options.mime.clone().or_else(|| detected.map(|detected| detected.mime.to_string()));
+
+    let size = match &data {
+        Some(data) => data.len() as u64,
+        None => file.metadata().await?.len(),
+    };
+
+    let source: Box<dyn AsyncRead + Unpin + Send> = match data {
+        Some(data) => Box::new(Cursor::new(data)),
+        None => Box::new(file),
+    };
+
+    let source = BufReader::new(source);
+
+    let reader: Box<dyn AsyncRead + Unpin + Send> = match options.compress {
+        Some(Compression::Zstd) => Box::new(ZstdEncoder::new(source)),
+        Some(Compression::Gzip) => Box::new(GzipEncoder::new(source)),
+        None => Box::new(source),
+    };
+
+    if let Some(compression) = options.compress {
+        file_name = format!("{file_name}.{}", compression.extension());
+    }
+
+    if options.encrypt {
+        let key = Key::generate();
+
+        Ok(Prepared {
+            body: Body::wrap_stream(crypt::encrypt_stream(reader, &key)),
+            file_name: format!("{file_name}.{}", crypt::EXTENSION),
+            key: Some(key),
+            // the contents are opaque ciphertext
+            mime: None,
+            notes,
+            size: None,
+        })
+    } else {
+        let body = Body::wrap_stream(ReaderStream::new(reader));
+        let mime = mime.filter(|_| options.compress.is_none());
+        let size = Some(size).filter(|_| options.compress.is_none());
+        Ok(Prepared { body, file_name, key: None, mime, notes, size })
+    }
+}
+
+fn with_extension(file_name: &str, extension: &str) -> String {
+    Path::new(file_name).with_extension(extension).to_string_lossy().into_owned()
+}
+
+/// Re-encode an image to fit the budget or strip its metadata
+///
+/// Re-encoded images never contain metadata.
+async fn transform_image(
+    mut data: Vec<u8>,
+    options: &UploadOptions,
+    file_name: &mut String,
+    notes: &mut Vec<String>
+) -> Result<Vec<u8>> {
+    if let Some(budget) = options.resize {
+        let resized;
+
+        (data, resized) = tokio::task::spawn_blocking(move || {
+            let resized = resize::fit(&data, &budget);
+            (data, resized)
+        }).await?;
+
+        if let Some(resized) = resized? {
+            let new_name = with_extension(file_name, resized.extension);
+
+            notes.push(
+                format!(
+                    "Re-encoded {file_name} to {new_name} ({}x{}, {} bytes)",
+                    resized.width,
+                    resized.height,
+                    resized.data.len()
+                )
+            );
+
+            *file_name = new_name;
+
+            return Ok(resized.data);
+        }
+
+        if !options.strip_metadata {
+            return Ok(data);
+        }
+    }
+
+    Ok(metadata::strip(&data)?.unwrap_or(data))
+}
@@ -0,0 +1,145 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Parsing and building of links to files, albums and Litterbox files
+//!
+//! Links are accepted with or without scheme, and with trailing slashes,
+//! query strings or fragments. Encryption keys in fragments are not part of the ID.
+
+use ::url::Url;
+use anyhow::{ Result, bail };
+use std::{ fmt, str::FromStr };
+
+use crate::id::{ AlbumShort, FileId };
+
+static CATBOX_HOST: &str = "catbox.moe";
+static CATBOX_FILES_HOST: &str = "files.catbox.moe";
+static LITTER_FILES_HOST: &str = "litter.catbox.moe";
+
+/// A link to something hosted on Catbox or Litterbox
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Link {
+    /// File on Catbox, e.g. `https://files.catbox.moe/abc123.png`
+    File(FileId),
+    /// Album on Catbox, e.g. `https://catbox.moe/c/abc123`
+    Album(AlbumShort),
+    /// Temporary file on Litterbox, e.g. `https://litter.catbox.moe/abc123.png`
+    Litter(FileId),
+}
+
+impl Link {
+    /// ID of the file or album
+    pub fn id(&self) -> &str {
+        match self {
+            Self::File(id) | Self::Litter(id) => id.as_str(),
+            Self::Album(short) => short.as_str(),
+        }
+    }
+
+    /// Build the canonical URL
+    pub fn url(&self) -> String {
+        match self {
+            Self::File(id) => file_url(id),
+            Self::Album(short) => album_url(short),
+            Self::Litter(id) => litter_url(id),
+        }
+    }
+}
+
+impl FromStr for Link {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let link = s.trim();
+
+        let url = if link.contains("://") {
+            Url::parse(link)?
+        } else {
+            Url::parse(&format!("https://{link}"))?
+        };
+
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+
+        let segments: Vec<_> = url
+            .path_segments()
+            .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+            .unwrap_or_default();
+
+        match (host, segments.as_slice()) {
+            (host, [id]) if host == CATBOX_FILES_HOST => Ok(Self::File(id.parse()?)),
+            (host, ["c", short]) if host == CATBOX_HOST => Ok(Self::Album(short.parse()?)),
+            (host, [id]) if host == LITTER_FILES_HOST => Ok(Self::Litter(id.parse()?)),
+            _ => bail!("{link} is not a link to a file or album on Catbox or Litterbox"),
+        }
+    }
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url())
+    }
+}
+
+/// Build the URL of a file on Catbox
+///
+/// # Arguments
+///
+/// * `id` - ID of the file, e.g. `abc123.png`
+pub fn file_url<S: AsRef<str>>(id: S) -> String {
+    format!("https://{CATBOX_FILES_HOST}/{}", id.as_ref())
+}
+
+/// Build the URL of an album
+///
+/// # Arguments
+///
+/// * `short` - ID of the album
+pub fn album_url<S: AsRef<str>>(short: S) -> String {
+    format!("https://{CATBOX_HOST}/c/{}", short.as_ref())
+}
+
+/// Build the URL of a file on Litterbox
+///
+/// # Arguments
+///
+/// * `id` - ID of the file, e.g. `abc123.png`
+pub fn litter_url<S: AsRef<str>>(id: S) -> String {
+    format!("https://{LITTER_FILES_HOST}/{}", id.as_ref())
+}
+
+/// Get the ID of a file on Catbox from its ID or link
+///
+/// # Arguments
+///
+/// * `file` - ID or link of the file
+pub fn file_id(file: &str) -> Result<FileId> {
+    if !is_link(file) {
+        return file.trim().parse();
+    }
+
+    match file.parse()? {
+        Link::File(id) => Ok(id),
+        _ => bail!("{file} is not a link to a file on Catbox"),
+    }
+}
+
+/// Get the ID of an album from its ID or link
+///
+/// # Arguments
+///
+/// * `album` - ID or link of the album
+pub fn album_short(album: &str) -> Result<AlbumShort> {
+    if !is_link(album) {
+        return album.trim().parse();
+    }
+
+    match album.parse()? {
+        Link::Album(short) => Ok(short),
+        _ => bail!("{album} is not a link to an album"),
+    }
+}
+
+/// IDs never contain slashes, so anything with one has to be a link
+fn is_link(s: &str) -> bool {
+    s.contains('/')
+}
@@ -1,10 +1,9 @@
 #![allow(clippy::missing_errors_doc)]
 
-use anyhow::Result;
+use anyhow::{ Result, bail };
 use clap::Parser;
 use futures::stream::{ FuturesUnordered, StreamExt };
-use regex::Regex;
-use std::{ path::Path, sync::LazyLock };
+use std::path::Path;
 use url::Url;
 
 use args::{
@@ -12,35 +11,63 @@ use args::{
     AlbumAdd,
     AlbumCommand,
     AlbumCreate,
-    AlbumDelete,
+    AlbumDownload,
     AlbumEdit,
     AlbumRemove,
+    AlbumShow,
     CatboxArgs,
     CatboxCommand,
     Delete,
+    Download,
+    Format,
     Litter,
     Upload,
 };
 
+use catbox::{
+    album::{ AlbumFile, Changes, Uploaded },
+    crypt,
+    download::{ DownloadOptions, Downloaded },
+    dry_run,
+    hash,
+    resize::{ Budget, CATBOX_MAX_SIZE },
+    upload::{ self, UploadOptions },
+    id::{ FileId, UserHash },
+    url::{ album_short, album_url, file_id, file_url },
+};
 #[cfg(not(test))]
-use catbox::{ album, file, litter };
+use catbox::{ album, download, file, litter };
 #[cfg(test)]
 mod test;
 #[cfg(test)]
-use test::catbox::{ album, file, litter };
+use test::catbox::{ album, download, file, litter };
 
 mod args;
+mod batch;
+mod delete;
+mod diff;
+mod ledger;
+mod reorder;
+mod reorganize;
+mod snapshot;
+mod sync;
+mod trash;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    match CatboxArgs::try_parse() {
-        Ok(args) =>
+    match CatboxArgs::try_parse_from(args::expand_argfiles(std::env::args_os())?) {
+        Ok(args) => {
+            dry_run::set(args.dry_run);
+
             match args.command {
                 CatboxCommand::Upload(sub_args) => upload(sub_args).await,
                 CatboxCommand::Delete(sub_args) => delete_file(sub_args).await,
                 CatboxCommand::Album(sub_args) => parse_album(sub_args).await,
                 CatboxCommand::Litter(sub_args) => litter(sub_args).await,
+                CatboxCommand::Download(sub_args) => download(sub_args).await,
+                CatboxCommand::Trash(sub_args) => trash::parse_trash(sub_args).await,
             }
+        }
         Err(args) => {
             println!("{args}");
 
@@ -49,25 +76,23 @@ async fn main() -> Result<()> {
     }
 }
 
-static RE_CATBOX: LazyLock<Regex> = LazyLock::new(||
-    Regex::new(r"^(https?://)?files.catbox.moe/.+").unwrap()
-);
-
-fn catbox_url_to_image_name(url: &str) -> String {
-    (if RE_CATBOX.is_match(url) { url.split('/').next_back().unwrap() } else { url }).to_string()
-}
-
-fn album_url_to_short(url: &str) -> String {
-    (if RE_CATBOX.is_match(url) { url.split('/').next_back().unwrap() } else { url }).to_string()
-}
-
 async fn parse_album(album_args: Album) -> Result<()> {
     match album_args.album_command {
         AlbumCommand::Create(sub_args) => create_album(sub_args).await,
-        AlbumCommand::Delete(sub_args) => delete_album(sub_args).await,
+        AlbumCommand::Delete(sub_args) => delete::delete_album(sub_args).await,
         AlbumCommand::Edit(sub_args) => edit_album(sub_args).await,
         AlbumCommand::Add(sub_args) => add_to_album(sub_args).await,
         AlbumCommand::Remove(sub_args) => remove_from_album(sub_args).await,
+        AlbumCommand::Show(sub_args) => show_album(sub_args).await,
+        AlbumCommand::Download(sub_args) => download_album(sub_args).await,
+        AlbumCommand::Sync(sub_args) => sync::sync_album(sub_args).await,
+        AlbumCommand::Diff(sub_args) => diff::diff_album(sub_args).await,
+        AlbumCommand::Clone(sub_args) => reorganize::clone_album(sub_args).await,
+        AlbumCommand::Merge(sub_args) => reorganize::merge_album(sub_args).await,
+        AlbumCommand::Split(sub_args) => reorganize::split_album(sub_args).await,
+        AlbumCommand::Reorder(sub_args) => reorder::reorder_album(sub_args).await,
+        AlbumCommand::Backup(sub_args) => snapshot::backup_album(sub_args).await,
+        AlbumCommand::Restore(sub_args) => snapshot::restore_album(sub_args).await,
     }
 }
 
@@ -78,8 +103,30 @@ async fn upload(upload_args: Upload) -> Result<()> {
 
     let (urls, rest): (Vec<_>, _) = rest.iter().partition(|uri| Url::parse(uri).is_ok());
 
+    if upload_args.naming.name.is_some() && files.len() > 1 {
+        bail!("--name can only be used when uploading a single file, use --name-template instead");
+    }
+
     let user = upload_args.user_hash;
 
+    let budget = Budget {
+        max_size: upload_args.max_size.map_or(CATBOX_MAX_SIZE, |size| size.min(CATBOX_MAX_SIZE)),
+        max_dimension: upload_args.max_dimension,
+    };
+
+    let options = UploadOptions {
+        encrypt: upload_args.encrypt,
+        compress: upload_args.compress,
+        strip_metadata: upload_args.strip_metadata,
+        resize: (upload_args.max_size.is_some() || upload_args.max_dimension.is_some()).then_some(
+            budget
+        ),
+        fix_extension: upload_args.fix_extension,
+        mime: upload_args.mime,
+        remote_name: upload_args.naming.remote_name(),
+    };
+    let share_page = upload_args.share_page;
+
     let print_result = |res| async move { println!("{res}") };
 
     tokio::join!(
@@ -90,12 +137,12 @@ async fn upload(upload_args: Upload) -> Result<()> {
             .for_each_concurrent(10, print_result),
         urls
             .into_iter()
-            .map(|url| upload_url(url.into(), &user))
+            .map(|url| upload_url(url.into(), user.as_ref(), &options))
             .collect::<FuturesUnordered<_>>()
             .for_each_concurrent(10, print_result),
         files
             .into_iter()
-            .map(|file| upload_file(file, &user))
+            .map(|file| upload_file(file, user.as_ref(), &options, share_page))
             .collect::<FuturesUnordered<_>>()
             .for_each_concurrent(10, print_result)
     );
@@ -107,34 +154,134 @@ async fn invalid_uri<S: Into<String>>(uri: S) -> String {
     format!("Ignoring {}: invalid path or URL", uri.into())
 }
 
-async fn upload_file<S: Into<String>>(file: String, user_hash: S) -> String {
-    file::from_file(&file, &user_hash.into()).await.unwrap_or_else(|_|
-        format!("Uploading {file} failed.")
-    )
+async fn upload_file(
+    file: String,
+    user_hash: Option<&UserHash>,
+    options: &UploadOptions,
+    share_page: bool
+) -> String {
+    let mut prepared = match upload::prepare(&file, options).await {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            return format!("Uploading {file} failed: {err}");
+        }
+    };
+
+    let notes = std::mem::take(&mut prepared.notes);
+
+    // the decrypted file keeps the remote name, so the page does not reveal the local one
+    let remote_name = prepared.file_name
+        .strip_suffix(&format!(".{}", crypt::EXTENSION))
+        .unwrap_or(&prepared.file_name)
+        .to_string();
+
+    let Ok(link) = file::upload_prepared(prepared, user_hash).await else {
+        return format!("Uploading {file} failed.");
+    };
+
+    if !link.starts_with("http") {
+        return link;
+    }
+
+    let mut notes = notes;
+    notes.extend(ledger::record(&file, &link, options).await.err().map(|err|
+        format!("Recording {file} in the ledger failed: {err}")
+    ));
+
+    let mut res = link.clone();
+
+    if options.compress.is_some() && let Ok(metadata) = tokio::fs::metadata(&file).await {
+        res = format!("{res} ({}, {} bytes uncompressed)", file_name(&file), metadata.len());
+    }
+
+    if share_page {
+        match file::share_page(link.as_str(), &remote_name, user_hash).await {
+            Ok(page) => res = format!("{res}\n{page}"),
+            Err(_) => res = format!("{res}\nUploading the share page for {file} failed."),
+        }
+    }
+
+    with_notes(res, notes)
 }
 
-async fn upload_url<S: Into<String>>(url: String, user_hash: S) -> String {
-    file::from_url(&url, &user_hash.into()).await.unwrap_or_else(|_|
+fn file_name(file_path: &str) -> String {
+    Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+async fn upload_url(
+    url: String,
+    user_hash: Option<&UserHash>,
+    options: &UploadOptions
+) -> String {
+    if options.encrypt {
+        return format!("Ignoring {url}: URL uploads cannot be encrypted");
+    }
+
+    if options.strip_metadata {
+        return format!("Ignoring {url}: metadata cannot be stripped from URL uploads");
+    }
+
+    if options.remote_name.is_some() {
+        return format!("Ignoring {url}: URL uploads keep their remote name");
+    }
+
+    if options.compress.is_some() {
+        return format!("Ignoring {url}: URL uploads cannot be compressed");
+    }
+
+    if options.resize.is_some() {
+        return format!("Ignoring {url}: URL uploads cannot be resized");
+    }
+
+    if options.fix_extension || options.mime.is_some() {
+        return format!("Ignoring {url}: URL uploads keep the type Catbox detects");
+    }
+
+    file::from_url(url.as_str(), user_hash).await.unwrap_or_else(|_|
         format!("Uploading {url} failed.")
     )
 }
 
-async fn upload_to_litter(file_path: String, time: u8) -> String {
-    litter
-        ::upload(&file_path, time).await
-        .unwrap_or_else(|_| format!("Uploading {file_path} failed."))
+async fn upload_to_litter(file_path: String, time: u8, options: &UploadOptions) -> String {
+    let mut prepared = match upload::prepare(&file_path, options).await {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            return format!("Uploading {file_path} failed: {err}");
+        }
+    };
+
+    let mut notes = std::mem::take(&mut prepared.notes);
+
+    let Ok(res) = litter::upload_prepared(prepared, time).await else {
+        return format!("Uploading {file_path} failed.");
+    };
+
+    if res.starts_with("http") && let Err(err) = ledger::record(&file_path, &res, options).await {
+        notes.push(format!("Recording {file_path} in the ledger failed: {err}"));
+    }
+
+    with_notes(res, notes)
+}
+
+fn with_notes(res: String, notes: Vec<String>) -> String {
+    notes.into_iter().fold(res, |res, note| format!("{res}\n{note}"))
 }
 
 async fn delete_file(delete_args: Delete) -> Result<()> {
-    let res = file::delete(
-        delete_args.files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect(),
-        delete_args.user_hash
-    ).await?;
+    let user_hash = args::required(delete_args.user_hash)?;
+    let mut files = delete_args.files;
+    files.extend(delete_args.input.read(file_id)?);
 
-    println!("{res}");
+    if delete_args.trash {
+        trash::save_files(&files, &delete::known_albums(None).await?).await?;
+    }
+
+    for batch in files.chunks(batch::DELETE_BATCH_SIZE) {
+        println!("{}", file::delete(batch, &user_hash).await?);
+    }
 
     Ok(())
 }
@@ -144,6 +291,18 @@ async fn litter(litter_args: Litter) -> Result<()> {
         .into_iter()
         .partition(|path| Path::new(&path).exists());
 
+    if litter_args.naming.name.is_some() && files.len() > 1 {
+        bail!("--name can only be used when uploading a single file, use --name-template instead");
+    }
+
+    let options = UploadOptions {
+        strip_metadata: litter_args.strip_metadata,
+        fix_extension: litter_args.fix_extension,
+        mime: litter_args.mime,
+        remote_name: litter_args.naming.remote_name(),
+        ..Default::default()
+    };
+
     let print_res = |res| async move { println!("{res}") };
 
     tokio::join!(
@@ -154,7 +313,7 @@ async fn litter(litter_args: Litter) -> Result<()> {
             .for_each_concurrent(10, print_res),
         files
             .into_iter()
-            .map(|file| upload_to_litter(file, litter_args.time.unwrap_or(1)))
+            .map(|file| upload_to_litter(file, litter_args.time.unwrap_or(1), &options))
             .collect::<FuturesUnordered<_>>()
             .for_each_concurrent(10, print_res)
     );
@@ -162,59 +321,230 @@ async fn litter(litter_args: Litter) -> Result<()> {
     Ok(())
 }
 
-async fn create_album(album_create_args: AlbumCreate) -> Result<()> {
-    let res = album::create(
-        album_create_args.title,
-        album_create_args.description.unwrap_or_default(),
-        album_create_args.user_hash,
-        album_create_args.files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect()
-    ).await?;
+async fn download(download_args: Download) -> Result<()> {
+    if download_args.name.is_some() && download_args.urls.len() > 1 {
+        bail!("--name can only be used when downloading a single file");
+    }
 
-    println!("{res}");
+    let dir = download_args.output;
+    let options = DownloadOptions {
+        decompress: download_args.decompress,
+        overwrite: download_args.overwrite,
+        name: download_args.name,
+    };
+
+    download_args.urls
+        .into_iter()
+        .map(|url| download_file(download::url(&url), &dir, &options))
+        .collect::<FuturesUnordered<_>>()
+        .for_each_concurrent(10, |res| async move { println!("{res}") }).await;
 
     Ok(())
 }
 
-async fn delete_album(album_delete_args: AlbumDelete) -> Result<()> {
-    let res = album::delete(
-        album_url_to_short(&album_delete_args.short),
-        album_delete_args.user_hash
-    ).await?;
+async fn download_file(url: String, dir: &str, options: &DownloadOptions) -> String {
+    let downloaded = match download::file_with(url.as_str(), dir, options).await {
+        Ok(downloaded) => downloaded,
+        Err(err) => {
+            return format!("Downloading {url} failed: {err}");
+        }
+    };
+
+    let mut res = if downloaded.skipped {
+        format!("{} skipped (already exists)", downloaded.path)
+    } else if downloaded.resumed > 0 {
+        format!("{} (resumed from {} bytes)", downloaded.path, downloaded.resumed)
+    } else {
+        downloaded.path.clone()
+    };
+
+    match verify(&url, &downloaded, options).await {
+        Ok(Some(true)) => res = format!("{res} (verified)"),
+        Ok(Some(false)) =>
+            res = format!(
+                "{res}\nWarning: {} does not match the SHA-256 hash of the uploaded file",
+                downloaded.path
+            ),
+        Ok(None) => (),
+        Err(err) => res = format!("{res}\nVerifying {} failed: {err}", downloaded.path),
+    }
 
-    println!("{res}");
+    res
+}
+
+/// Compare a download with the hash of the local file recorded in the ledger
+///
+/// Returns `None` if the file is not in the ledger, or if the download cannot match the local file
+async fn verify(
+    url: &str,
+    downloaded: &Downloaded,
+    options: &DownloadOptions
+) -> Result<Option<bool>> {
+    let url = Url::parse(url)?;
+
+    let Some(id) = url.path_segments().and_then(|mut segments| segments.next_back()) else {
+        return Ok(None);
+    };
+
+    let Some(entry) = ledger::find_id(id)? else {
+        return Ok(None);
+    };
+
+    let complete = !entry.altered &&
+        (!entry.encrypted || url.fragment().is_some()) &&
+        (entry.compression.is_none() || options.decompress);
+
+    if !complete {
+        return Ok(None);
+    }
+
+    Ok(Some(hash::file(&downloaded.path).await? == entry.sha256))
+}
+
+async fn create_album(album_create_args: AlbumCreate) -> Result<()> {
+    let mut files = album_create_args.files;
+    files.extend(album_create_args.input.read(args::album_file)?);
+
+    batch::create(
+        &album_create_args.title,
+        &album_create_args.description.unwrap_or_default(),
+        album_create_args.user_hash.as_ref(),
+        &files,
+        &UploadOptions::default()
+    ).await?;
 
     Ok(())
 }
 
+/// Record the files of an album in the ledger, so deleting other albums keeps them
+///
+/// # Arguments
+///
+/// * `album` - ID or URL of the album, e.g. the response to creating it
+/// * `title` - Title of the album
+/// * `files` - Files of the album, in order
+fn record_album(album: &str, title: &str, files: &[FileId]) {
+    let Ok(short) = album_short(album.trim()) else {
+        return;
+    };
+
+    if let Err(err) = ledger::record_album(&short, title, files) {
+        println!("Recording {} in the ledger failed: {err}", album_url(&short));
+    }
+}
+
+/// Print and record files which were uploaded for an album
+async fn record_uploads(uploaded: &[Uploaded], options: &UploadOptions) {
+    for uploaded in uploaded {
+        println!("{} ({})", uploaded.link, uploaded.path);
+
+        if let Err(err) = ledger::record(&uploaded.path, &uploaded.link, options).await {
+            println!("Recording {} in the ledger failed: {err}", uploaded.path);
+        }
+    }
+}
+
 async fn edit_album(album_edit_args: AlbumEdit) -> Result<()> {
-    let res = album::edit(
-        album_url_to_short(&album_edit_args.short),
-        album_edit_args.title,
-        album_edit_args.description.unwrap_or_default(),
-        album_edit_args.user_hash,
-        album_edit_args.files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect()
+    let current = album::show(&album_edit_args.short).await?;
+    let mut updated = current.clone();
+
+    if let Some(title) = album_edit_args.title {
+        updated.title = title;
+    }
+
+    if let Some(description) = album_edit_args.description {
+        updated.description = description;
+    }
+
+    let explicit = !album_edit_args.files.is_empty();
+
+    if explicit {
+        updated.files = album_edit_args.files;
+    }
+
+    for file in album_edit_args.add {
+        if !updated.files.contains(&file) {
+            updated.files.push(file);
+        }
+    }
+
+    updated.files.retain(|file| !album_edit_args.remove.contains(file));
+
+    // an edit replaces all files, so files missing from the parsed page would be removed
+    if !explicit && current.files.is_empty() {
+        bail!("No files were found on {}, give all files the album should contain", current.url());
+    }
+
+    if !explicit && updated.files.is_empty() {
+        bail!("Refusing to remove all files from {}", current.url());
+    }
+
+    let changes = Changes::between(&current, &updated);
+
+    if changes.is_empty() && updated.files == current.files {
+        println!("Nothing to change in {}", current.url());
+        return Ok(());
+    }
+
+    if changes.is_empty() {
+        println!("Reordering files");
+    } else {
+        println!("{changes}");
+    }
+
+    if album_edit_args.preview {
+        return Ok(());
+    }
+
+    let user_hash = args::required(album_edit_args.user_hash)?;
+    let res = batch::edit(
+        &updated.short,
+        &updated.title,
+        &updated.description,
+        &user_hash,
+        &updated.files
     ).await?;
 
+    record_album(updated.short.as_str(), &updated.title, &updated.files);
+
     println!("{res}");
 
     Ok(())
 }
 
 async fn add_to_album(album_add_args: AlbumAdd) -> Result<()> {
-    let res = album::add_files(
-        album_url_to_short(&album_add_args.short),
-        album_add_args.user_hash,
-        album_add_args.files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect()
-    ).await?;
+    let user_hash = args::required(album_add_args.user_hash)?;
+    let options = UploadOptions::default();
+    let mut files = Vec::new();
+
+    let mut input = album_add_args.files;
+    input.extend(album_add_args.input.read(args::album_file)?);
+
+    for file in input {
+        let AlbumFile::Local(path) = &file else {
+            files.push(file);
+            continue;
+        };
+
+        if let Some(entry) = ledger::find_upload(path).await? {
+            println!("Reusing {} for {path}", entry.url);
+            files.push(AlbumFile::Remote(file_id(&entry.url)?));
+        } else {
+            files.push(file);
+        }
+    }
+
+    let (res, ids) = batch::add(&album_add_args.short, &user_hash, &files, &options).await?;
+
+    if let Some(mut entry) = ledger::find_album(&album_add_args.short)? {
+        for file in ids {
+            if !entry.files.contains(&file) {
+                entry.files.push(file);
+            }
+        }
+
+        record_album(entry.album.as_str(), &entry.title, &entry.files);
+    }
 
     println!("{res}");
 
@@ -222,23 +552,110 @@ async fn add_to_album(album_add_args: AlbumAdd) -> Result<()> {
 }
 
 async fn remove_from_album(album_remove_args: AlbumRemove) -> Result<()> {
-    let res = album::remove_files(
-        album_url_to_short(&album_remove_args.short),
-        album_remove_args.user_hash,
-        album_remove_args.files
-            .into_iter()
-            .map(|file| catbox_url_to_image_name(&file))
-            .collect()
-    ).await?;
+    let user_hash = args::required(album_remove_args.user_hash)?;
+    let mut files = Vec::new();
+
+    let mut input = album_remove_args.files;
+    input.extend(album_remove_args.input.read(args::album_file)?);
+
+    for file in input {
+        match file {
+            AlbumFile::Remote(id) => files.push(id),
+            AlbumFile::Local(path) => {
+                let Some(entry) = ledger::find_path(&path).await? else {
+                    bail!("{path} has no recorded upload, remove it by its Catbox ID instead");
+                };
+
+                files.push(file_id(&entry.url)?);
+            }
+        }
+    }
+
+    let mut res = String::new();
+
+    for batch in files.chunks(batch::ALBUM_BATCH_SIZE) {
+        res = album::remove_files(
+            &album_remove_args.short,
+            &user_hash,
+            batch
+        ).await?;
+    }
+
+    if let Some(mut entry) = ledger::find_album(&album_remove_args.short)? {
+        entry.files.retain(|file| !files.contains(file));
+        record_album(entry.album.as_str(), &entry.title, &entry.files);
+    }
 
     println!("{res}");
 
     Ok(())
 }
 
+async fn show_album(album_show_args: AlbumShow) -> Result<()> {
+    let info = album::show(&album_show_args.short).await?;
+
+    match album_show_args.format {
+        Format::Text => {
+            println!("{} ({})", info.title, info.url());
+
+            if !info.description.is_empty() {
+                println!("{}", info.description);
+            }
+
+            println!("\n{} files:", info.files.len());
+
+            for file in &info.files {
+                println!("{}", file_url(file));
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+    }
+
+    Ok(())
+}
+
+async fn download_album(album_download_args: AlbumDownload) -> Result<()> {
+    let info = album::show(&album_download_args.short).await?;
+
+    let dir = if album_download_args.subfolder {
+        Path::new(&album_download_args.output)
+            .join(folder_name(&info.title).unwrap_or_else(|| info.short.to_string()))
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        album_download_args.output
+    };
+
+    let options = DownloadOptions {
+        overwrite: album_download_args.overwrite,
+        ..Default::default()
+    };
+
+    info.files
+        .iter()
+        .map(|file| download_file(file_url(file), &dir, &options))
+        .collect::<FuturesUnordered<_>>()
+        .for_each_concurrent(10, |res| async move { println!("{res}") }).await;
+
+    Ok(())
+}
+
+/// Turn an album title into a folder name, replacing characters which are not allowed in paths
+fn folder_name(title: &str) -> Option<String> {
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_control() || r#"/\:*?"<>|"#.contains(c) { '_' } else { c })
+        .collect();
+
+    let name = name.trim().trim_matches('.').to_string();
+
+    (!name.is_empty()).then_some(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use catbox::{ naming::RemoteName, upload::Compression };
     use std::io::Write;
     use tempfile::Builder;
 
@@ -247,6 +664,8 @@ mod tests {
 
     #[tokio::test]
     async fn upload_file() -> Result<()> {
+        let _state = test::isolate();
+
         let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
         write!(file, "content").unwrap();
 
@@ -265,6 +684,8 @@ mod tests {
 
     #[tokio::test]
     async fn upload_url() -> Result<()> {
+        let _state = test::isolate();
+
         let args = CatboxArgs::parse_from(vec!["catbox", "upload", "--user", "123456", FILE_URL]);
 
         if let CatboxCommand::Upload(upload_args) = args.command {
@@ -273,11 +694,28 @@ mod tests {
             panic!("Invalid subcommand");
         }
 
+        // remote files are uploaded by Catbox as they are, options for local files are refused
+        let refused = [
+            UploadOptions { strip_metadata: true, ..Default::default() },
+            UploadOptions { remote_name: Some(RemoteName::Anonymous), ..Default::default() },
+            UploadOptions { compress: Some(Compression::Zstd), ..Default::default() },
+            UploadOptions { resize: Some(Budget::default()), ..Default::default() },
+            UploadOptions { fix_extension: true, ..Default::default() },
+            UploadOptions { mime: Some("image/png".to_string()), ..Default::default() },
+        ];
+
+        for options in refused {
+            let res = super::upload_url(FILE_URL.to_string(), None, &options).await;
+            assert!(res.starts_with(&format!("Ignoring {FILE_URL}: ")), "{res}");
+        }
+
         Ok(())
     }
 
     #[tokio::test]
     async fn nonexistant() -> Result<()> {
+        let _state = test::isolate();
+
         let args = CatboxArgs::parse_from(
             vec!["catbox", "upload", "--user", "123456", "This is not a file or url"]
         );
@@ -293,6 +731,8 @@ mod tests {
 
     #[tokio::test]
     async fn upload_multi() -> Result<()> {
+        let _state = test::isolate();
+
         let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
         write!(file, "content").unwrap();
 
@@ -320,6 +760,8 @@ mod tests {
 
     #[tokio::test]
     async fn delete_files() -> Result<()> {
+        let _state = test::isolate();
+
         let args = CatboxArgs::parse_from(
             vec!["catbox", "delete", "--user", "123456", "file.png", "another.jpg"]
         );
@@ -330,11 +772,322 @@ mod tests {
             panic!("Invalid subcommand");
         }
 
+        let mut list = Builder::new().suffix(".txt").tempfile()?;
+        let ids: Vec<_> = (0..250).map(|i| format!("file{i}.png")).collect();
+        write!(list, "{}\0", ids.join("\0"))?;
+        let list_path = list.path().to_string_lossy().into_owned();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "delete", "-u", "1", "-0", "--from-file", &list_path, "more.png"]
+        );
+
+        if let CatboxCommand::Delete(delete_args) = args.command {
+            assert_eq!(delete_args.input.read(file_id)?.len(), 250);
+            delete_file(delete_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let invalid = vec!["catbox", "delete", "-u", "1", "-0", "file.png"];
+        assert!(CatboxArgs::try_parse_from(invalid).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn argfiles() -> Result<()> {
+        let mut argfile = Builder::new().suffix(".txt").tempfile()?;
+        writeln!(argfile, "abc123.png\n\n  https://files.catbox.moe/def456.png  ")?;
+
+        let argfile = format!("@{}", argfile.path().display());
+        let args = ["catbox", "delete", "-u", "1", &argfile, "@@literal"].map(Into::into);
+
+        let expanded = args::expand_argfiles(args)?;
+        let expanded: Vec<_> = expanded.iter().map(|arg| arg.to_string_lossy()).collect();
+
+        assert_eq!(
+            expanded,
+            vec![
+                "catbox",
+                "delete",
+                "-u",
+                "1",
+                "abc123.png",
+                "https://files.catbox.moe/def456.png",
+                "@literal"
+            ]
+        );
+
+        assert!(args::expand_argfiles(["@/nonexistent/list.txt".into()]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_requests() {
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "delete", "-u", "123456", "--dry-run", "a.png"]
+        );
+        assert!(args.dry_run);
+
+        let fields = [("reqtype", "deletefiles"), ("userhash", "123456"), ("files", "a.png b.jpg")];
+
+        assert_eq!(
+            dry_run::describe("https://catbox.moe/user/api.php", &fields, None),
+            "Dry run: POST https://catbox.moe/user/api.php\n  \
+            reqtype: deletefiles\n  userhash: <redacted>\n  files: a.png b.jpg"
+        );
+
+        let fields = [("reqtype", "fileupload"), ("userhash", "")];
+        let file = Some(("a.png", Some(42)));
+
+        assert_eq!(
+            dry_run::describe("https://catbox.moe/user/api.php", &fields, file),
+            "Dry run: POST https://catbox.moe/user/api.php\n  \
+            reqtype: fileupload\n  userhash: \n  fileToUpload: a.png (42 bytes)"
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_encrypted() -> Result<()> {
+        let _state = test::isolate();
+
+        let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
+        write!(file, "content").unwrap();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "upload", "-u", "123456", "-e", file.path().to_str().unwrap(), FILE_URL]
+        );
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert!(upload_args.encrypt);
+            upload(upload_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_share_page() -> Result<()> {
+        let _state = test::isolate();
+
+        let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
+        write!(file, "content").unwrap();
+
+        let path = file.path().to_str().unwrap();
+
+        let without_encryption = vec!["catbox", "upload", "-u", "1", "--share-page", path];
+        assert!(CatboxArgs::try_parse_from(without_encryption).is_err());
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "upload", "-u", "123456", "--encrypt", "--share-page", path]
+        );
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            upload(upload_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_compressed() -> Result<()> {
+        let _state = test::isolate();
+
+        let mut file = Builder::new().suffix(".log").tempfile().unwrap();
+        write!(file, "content").unwrap();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "upload", "-u", "1", "--compress", "zstd", file.path().to_str().unwrap()]
+        );
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert_eq!(upload_args.compress, Some(Compression::Zstd));
+            upload(upload_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let invalid = vec!["catbox", "upload", "-u", "1", "-c", "lzma", "file"];
+        assert!(CatboxArgs::try_parse_from(invalid).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_resized() -> Result<()> {
+        let _state = test::isolate();
+
+        let file = Builder::new().suffix(".png").tempfile().unwrap();
+        image::RgbImage::from_fn(64, 32, |x, y| image::Rgb([x as u8, y as u8, 0]))
+            .save(file.path())?;
+
+        let args = CatboxArgs::parse_from(
+            vec![
+                "catbox",
+                "upload",
+                "-u",
+                "123456",
+                "--max-size",
+                "10M",
+                "--max-dimension",
+                "16",
+                file.path().to_str().unwrap()
+            ]
+        );
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert_eq!(upload_args.max_size, Some(10 * 1024 * 1024));
+            upload(upload_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let invalid = vec!["catbox", "upload", "-u", "1", "--max-size", "10X", "file"];
+        assert!(CatboxArgs::try_parse_from(invalid).is_err());
+
+        let overflow = vec!["catbox", "upload", "-u", "1", "--max-size", "99999999999G", "file"];
+        assert!(CatboxArgs::try_parse_from(overflow).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_fix_extension() -> Result<()> {
+        let _state = test::isolate();
+
+        let file = Builder::new().suffix(".tmp").tempfile().unwrap();
+        image::RgbImage::new(4, 4).save_with_format(file.path(), image::ImageFormat::Png)?;
+
+        let path = file.path().to_str().unwrap();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "upload", "-u", "123456", "--fix-extension", "--mime", "image/png", path]
+        );
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            let options = UploadOptions { fix_extension: true, ..Default::default() };
+            let prepared = upload::prepare(path, &options).await?;
+
+            assert!(prepared.file_name.ends_with(".png"));
+            assert_eq!(prepared.mime.as_deref(), Some("image/png"));
+
+            upload(upload_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upload_renamed() -> Result<()> {
+        let _state = test::isolate();
+
+        let mut file = Builder::new().suffix(".pdf").tempfile().unwrap();
+        write!(file, "content").unwrap();
+
+        let path = file.path().to_str().unwrap();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "upload", "-u", "1", "--name-template", "{hash8}.{ext}", path]
+        );
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            let options = UploadOptions {
+                remote_name: upload_args.naming.remote_name(),
+                ..Default::default()
+            };
+
+            assert_eq!(upload::prepare(path, &options).await?.file_name, "ed7002b4.pdf");
+
+            upload(upload_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "upload", "-u", "1", "--name", "a", path, path]
+        );
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert!(upload(upload_args).await.is_err());
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let conflicting = vec!["catbox", "upload", "-u", "1", "--name", "a", "--anonymize-names"];
+        assert!(CatboxArgs::try_parse_from(conflicting).is_err());
+
+        let unknown = vec!["catbox", "upload", "-u", "1", "--name-template", "{user}.{ext}", path];
+        assert!(CatboxArgs::try_parse_from(unknown).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_files() -> Result<()> {
+        let _state = test::isolate();
+
+        let args = CatboxArgs::parse_from(
+            vec![
+                "catbox",
+                "download",
+                "--output",
+                "/tmp",
+                "--decompress",
+                "https://files.catbox.moe/abc123.txt",
+                "https://files.catbox.moe/ghi789.log.zst",
+                "https://files.catbox.moe/def456.txt.enc#key"
+            ]
+        );
+
+        if let CatboxCommand::Download(download_args) = args.command {
+            download(download_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_verified() -> Result<()> {
+        let _state = test::isolate();
+
+        let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
+        writeln!(file, "verify me")?;
+
+        let path = file.path().to_str().unwrap();
+        let dir = file.path().parent().unwrap().to_str().unwrap();
+        let id = file_name(path);
+
+        let options = UploadOptions::default();
+        let link = super::upload_file(path.to_string(), None, &options, false).await;
+        assert_eq!(ledger::find_id(&id)?.map(|entry| entry.url), Some(link));
+
+        let res = download_file(download::url(&id), dir, &DownloadOptions::default()).await;
+        assert_eq!(res, format!("{path} (verified)"));
+
+        let args = CatboxArgs::parse_from(vec!["catbox", "download", "--name", "a", "b", "c"]);
+
+        if let CatboxCommand::Download(download_args) = args.command {
+            assert!(download(download_args).await.is_err());
+        } else {
+            panic!("Invalid subcommand");
+        }
+
         Ok(())
     }
 
     #[tokio::test]
     async fn album_create() -> Result<()> {
+        let _state = test::isolate();
+
         let args = CatboxArgs::parse_from(
             vec![
                 "catbox",
@@ -357,11 +1110,35 @@ mod tests {
             panic!("Invalid subcommand");
         }
 
+        let mut file = Builder::new().prefix("photo").suffix(".jpg").tempfile().unwrap();
+        writeln!(file, "not really a photo")?;
+
+        let path = file.path().to_str().unwrap();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "album", "create", "-u", "123456", "-t", "Photos", path, "abc123.png"]
+        );
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            if let AlbumCommand::Create(create_args) = &album_args.album_command {
+                let remote = AlbumFile::Remote("abc123.png".parse()?);
+                assert_eq!(create_args.files, vec![AlbumFile::Local(path.to_string()), remote]);
+            }
+
+            parse_album(album_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        assert!(ledger::find_id(&file_name(path))?.is_some());
+
         Ok(())
     }
 
     #[tokio::test]
     async fn album_add() -> Result<()> {
+        let _state = test::isolate();
+
         let args = CatboxArgs::parse_from(
             vec!["catbox", "album", "add", "--user", "123456", "--short", "123asd", "file.png"]
         );
@@ -372,11 +1149,69 @@ mod tests {
             panic!("Invalid subcommand");
         }
 
+        let mut file = Builder::new().prefix("added").suffix(".txt").tempfile().unwrap();
+        writeln!(file, "already uploaded")?;
+
+        let path = file.path().to_str().unwrap();
+        let options = UploadOptions::default();
+        let link = super::upload_file(path.to_string(), None, &options, false).await;
+
+        assert_eq!(ledger::find_upload(path).await?.map(|entry| entry.url), Some(link.clone()));
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "album", "add", "-u", "123456", "-s", "123asd", path, "def456.png"]
+        );
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            parse_album(album_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let mut list = Builder::new().suffix(".txt").tempfile()?;
+        writeln!(list, "{path}")?;
+
+        for i in 0..150 {
+            writeln!(list, "https://files.catbox.moe/file{i}.png")?;
+        }
+
+        let list_path = list.path().to_string_lossy().into_owned();
+
+        for (command, album) in [
+            ("create", ["-t", "Bulk"]),
+            ("add", ["-s", "123asd"]),
+            ("remove", ["-s", "123asd"]),
+        ] {
+            let mut command = vec!["catbox", "album", command, "-u", "1"];
+            command.extend(album);
+            command.extend(["--from-file", &list_path]);
+
+            let args = CatboxArgs::parse_from(command);
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                parse_album(album_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        // the bulk album was created with the reused upload and the listed files
+        let bulk = ledger::find_album(&album_short("123435")?)?.unwrap();
+        assert_eq!((bulk.title.as_str(), bulk.files.len()), ("Bulk", 151));
+        assert_eq!(bulk.files[0], file_id(&link)?);
+
+        let missing = args::album_file("./photos/new.jpg").unwrap_err();
+        assert_eq!(missing.to_string(), "./photos/new.jpg was not found");
+        assert!(args::album_file("https://example.com/new.jpg").is_err());
+        assert!(matches!(args::album_file("abc123.jpg")?, AlbumFile::Remote(_)));
+
         Ok(())
     }
 
     #[tokio::test]
     async fn album_remove() -> Result<()> {
+        let _state = test::isolate();
+
         let args = CatboxArgs::parse_from(
             vec!["catbox", "album", "remove", "--user", "123456", "--short", "123asd", "file.png"]
         );
@@ -387,13 +1222,120 @@ mod tests {
             panic!("Invalid subcommand");
         }
 
+        let mut file = Builder::new().prefix("removed").suffix(".txt").tempfile().unwrap();
+        writeln!(file, "never uploaded")?;
+
+        let path = file.path().to_str().unwrap();
+        let remove = vec!["catbox", "album", "remove", "-u", "123456", "-s", "123asd", path];
+
+        if let CatboxCommand::Album(album_args) = CatboxArgs::parse_from(remove.clone()).command {
+            assert!(parse_album(album_args).await.is_err());
+        }
+
+        super::upload_file(path.to_string(), None, &UploadOptions::default(), false).await;
+
+        if let CatboxCommand::Album(album_args) = CatboxArgs::parse_from(remove).command {
+            parse_album(album_args).await?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_links() -> Result<()> {
+        use catbox::url::{ Link, album_short, file_id };
+
+        for file in [
+            "abc123.png",
+            "https://files.catbox.moe/abc123.png",
+            "files.catbox.moe/abc123.png/",
+            "http://files.catbox.moe/abc123.png?download=1#key",
+        ] {
+            assert_eq!(file_id(file)?.as_str(), "abc123.png");
+        }
+
+        for album in ["asd123", "https://catbox.moe/c/asd123/", "www.catbox.moe/c/asd123?x=y"] {
+            assert_eq!(album_short(album)?.as_str(), "asd123");
+        }
+
+        let litter: Link = "litter.catbox.moe/xyz.zip".parse()?;
+        assert_eq!(litter, Link::Litter("xyz.zip".parse()?));
+        assert_eq!(litter.to_string(), "https://litter.catbox.moe/xyz.zip");
+
+        assert!(file_id("https://catbox.moe/c/asd123").is_err());
+        assert!(album_short("https://files.catbox.moe/abc123.png").is_err());
+        assert!("https://example.com/abc123.png".parse::<Link>().is_err());
+        assert!(file_id("files.catbox.moe/abc 123.png").is_err());
+
+        assert_eq!(download::url("catbox.moe/c/asd123"), "https://catbox.moe/c/asd123");
+        assert_eq!(download::url("abc123.png"), "https://files.catbox.moe/abc123.png");
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate_ids() {
+        let args = CatboxArgs::parse_from(vec!["catbox", "delete", "-u", "secret1", "abc123.png"]);
+
+        assert!(!format!("{args:?}").contains("secret1"));
+        assert_eq!(args.user_hash.map(|user_hash| user_hash.to_string()), Some("secret1".into()));
+
+        // an empty hash means anonymous requests
+        let args = CatboxArgs::parse_from(vec!["catbox", "upload", "-u", "", "file.png"]);
+
+        if let CatboxCommand::Upload(upload_args) = args.command {
+            assert_eq!(upload_args.user_hash, None);
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "album", "create", "-t", "Anonymous", "a.png"]
+        );
+        assert_eq!(args.user_hash, None);
+        assert!(args::required(args.user_hash).is_err());
+
+        for invalid in [
+            vec!["catbox", "delete", "-u", "1", "abc 123.png"],
+            vec!["catbox", "delete", "-u", "not a hash", "abc123.png"],
+            vec!["catbox", "album", "delete", "-u", "1", "abc/123"],
+            vec!["catbox", "album", "add", "-u", "1", "-s", "https://catbox.moe/c/", "abc123.png"],
+        ] {
+            assert!(CatboxArgs::try_parse_from(invalid).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn album_show() -> Result<()> {
+        let _state = test::isolate();
+
+        for format in ["text", "json"] {
+            let args = CatboxArgs::parse_from(
+                vec!["catbox", "album", "show", "--format", format, "https://catbox.moe/c/asd123"]
+            );
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                parse_album(album_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        let invalid = vec!["catbox", "album", "show", "--format", "xml", "asd123"];
+        assert!(CatboxArgs::try_parse_from(invalid).is_err());
+
         Ok(())
     }
 
     #[tokio::test]
-    async fn album_delete() -> Result<()> {
+    async fn album_download() -> Result<()> {
+        let _state = test::isolate();
+
+        assert_eq!(folder_name("Trip: 2024/05"), Some("Trip_ 2024_05".to_string()));
+        assert_eq!(folder_name(" .. "), None);
+
         let args = CatboxArgs::parse_from(
-            vec!["catbox", "album", "delete", "--user", "123345", "asd123"]
+            vec!["catbox", "album", "download", "-o", "/tmp", "--subfolder", "asd123"]
         );
 
         if let CatboxCommand::Album(album_args) = args.command {
@@ -407,6 +1349,8 @@ mod tests {
 
     #[tokio::test]
     async fn album_edit() -> Result<()> {
+        let _state = test::isolate();
+
         let args = CatboxArgs::parse_from(
             vec![
                 "catbox",
@@ -431,11 +1375,79 @@ mod tests {
             panic!("Invalid subcommand");
         }
 
+        let args = CatboxArgs::parse_from(
+            vec![
+                "catbox",
+                "album",
+                "edit",
+                "--user",
+                "123345",
+                "--short",
+                "https://catbox.moe/c/asd123",
+                "--add",
+                "ghi789.gif,abc123.jpg",
+                "--remove",
+                "https://files.catbox.moe/def456.png"
+            ]
+        );
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            if let AlbumCommand::Edit(edit_args) = &album_args.album_command {
+                assert_eq!(edit_args.title, None);
+                assert_eq!(edit_args.add, vec!["ghi789.gif".parse()?, "abc123.jpg".parse()?]);
+            }
+
+            parse_album(album_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let conflicting = vec![
+            "catbox",
+            "album",
+            "edit",
+            "-u",
+            "1",
+            "-s",
+            "asd123",
+            "--add",
+            "a.png",
+            "b.png"
+        ];
+        assert!(CatboxArgs::try_parse_from(conflicting).is_err());
+
+        // previews only read the public album page, so they need no user hash
+        let anonymous = vec!["catbox", "album", "edit", "-u", "", "-s", "asd123", "-t", "New"];
+
+        for (preview, ok) in [(Some("--preview"), true), (None, false)] {
+            let args = CatboxArgs::parse_from(anonymous.iter().copied().chain(preview));
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                assert_eq!(parse_album(album_args).await.is_ok(), ok);
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        // all files are only removed when they are replaced explicitly
+        let remove_all = "abc123.jpg,def456.png";
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "album", "edit", "-u", "1", "-s", "asd123", "--remove", remove_all]
+        );
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            assert!(parse_album(album_args).await.is_err());
+        } else {
+            panic!("Invalid subcommand");
+        }
+
         Ok(())
     }
 
     #[tokio::test]
     async fn upload_litter() -> Result<()> {
+        let _state = test::isolate();
+
         let mut file = Builder::new().suffix(".txt").tempfile().unwrap();
         write!(file, "content").unwrap();
 
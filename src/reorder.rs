@@ -0,0 +1,203 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Ordering the files of an album
+//!
+//! Files are sorted by name, upload date, size or the date photos were taken,
+//! or ordered as listed in a file.
+
+use anyhow::{ Result, bail };
+use catbox::{ id::FileId, metadata, url::{ file_id, file_url } };
+use futures::stream::{ self, StreamExt };
+
+#[cfg(not(test))]
+use catbox::{ album, download };
+#[cfg(test)]
+use crate::test::catbox::{ album, download };
+
+use crate::{
+    args::{ self, AlbumReorder, SortBy },
+    batch,
+    file_name,
+    ledger,
+    record_album,
+};
+
+/// Change the order of the files of an album
+pub async fn reorder_album(album_reorder_args: AlbumReorder) -> Result<()> {
+    let user_hash = args::required(album_reorder_args.user_hash)?;
+    let current = album::show(&album_reorder_args.short).await?;
+
+    let mut files = match (album_reorder_args.sort, album_reorder_args.order_from) {
+        (_, Some(order_from)) => order_from_file(&current.files, &order_from)?,
+        (Some(sort), None) => sort_files(&current.files, sort).await?,
+        (None, None) => current.files.clone(),
+    };
+
+    if album_reorder_args.reverse {
+        files.reverse();
+    }
+
+    if files == current.files {
+        println!("Nothing to change in {}", current.url());
+        return Ok(());
+    }
+
+    println!("Reordering files");
+
+    let res = batch::edit(
+        &current.short,
+        &current.title,
+        &current.description,
+        &user_hash,
+        &files
+    ).await?;
+
+    record_album(current.short.as_str(), &current.title, &files);
+
+    println!("{res}");
+
+    Ok(())
+}
+
+/// Order files as listed in a file, files which are not listed keep their order at the end
+///
+/// Empty lines and lines starting with `#` are skipped.
+fn order_from_file(files: &[FileId], order_from: &str) -> Result<Vec<FileId>> {
+    let mut ordered = Vec::new();
+
+    for line in std::fs::read_to_string(order_from)?.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let id = file_id(line)?;
+
+        if !files.contains(&id) {
+            bail!("{id} is not in the album");
+        }
+
+        if !ordered.contains(&id) {
+            ordered.push(id);
+        }
+    }
+
+    let unlisted: Vec<_> = files
+        .iter()
+        .filter(|file| !ordered.contains(file))
+        .cloned()
+        .collect();
+
+    ordered.extend(unlisted);
+
+    Ok(ordered)
+}
+
+/// Sort files by information fetched from Catbox, keeping files without it in order at the end
+async fn sort_files(files: &[FileId], sort: SortBy) -> Result<Vec<FileId>> {
+    // EXIF data comes before the image data, so the beginning of a file is enough
+    const EXIF_BYTES: u64 = 256 * 1024;
+
+    let mut keyed: Vec<(Option<String>, FileId)> = Vec::new();
+
+    if sort == SortBy::Name {
+        for file in files {
+            let name = ledger::find_id(file.as_str())?.map_or_else(
+                || file.to_string(),
+                |entry| file_name(&entry.path)
+            );
+
+            keyed.push((Some(name.to_lowercase()), file.clone()));
+        }
+    } else {
+        let bytes = if sort == SortBy::ExifDate { EXIF_BYTES } else { 1 };
+
+        let peeks: Vec<_> = stream::iter(files)
+            .map(|file| download::peek(file_url(file), bytes))
+            .buffered(10)
+            .collect().await;
+
+        for (file, peek) in files.iter().zip(peeks) {
+            let peek = peek?;
+
+            // zero padded, so the numbers sort as text
+            let key = match sort {
+                SortBy::Date => peek.modified.map(|modified| format!("{modified:020}")),
+                SortBy::Size => peek.size.map(|size| format!("{size:020}")),
+                _ => metadata::exif_date(&peek.head),
+            };
+
+            keyed.push((key, file.clone()));
+        }
+    }
+
+    // stable, so files with equal keys keep their order
+    keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+
+    Ok(keyed.into_iter().map(|(_, file)| file).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ args::{ CatboxArgs, CatboxCommand }, parse_album, test };
+    use clap::Parser;
+    use std::io::Write;
+    use tempfile::Builder;
+
+    #[tokio::test]
+    async fn album_reorder() -> Result<()> {
+        let _state = test::isolate();
+
+        // big endian TIFF with a DateTime entry in the first IFD and no further IFDs
+        let mut tiff = b"MM\0\x2a\0\0\0\x08\0\x01".to_vec();
+        tiff.extend_from_slice(b"\x01\x32\0\x02\0\0\0\x14\0\0\0\x1a\0\0\0\0");
+        tiff.extend_from_slice(b"2024:05:01 12:00:00\0");
+
+        let mut jpeg = b"\xff\xd8\xff\xe1".to_vec();
+        jpeg.extend_from_slice(&u16::try_from(tiff.len() + 8)?.to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(b"\xff\xda");
+
+        assert_eq!(metadata::exif_date(&jpeg), Some("2024:05:01 12:00:00".to_string()));
+        assert_eq!(metadata::exif_date(b"\xff\xd8\xff\xda"), None);
+
+        let files: Vec<FileId> = vec!["abc123.jpg".parse()?, "def456.png".parse()?];
+
+        let sorted = sort_files(&files, SortBy::Date).await?;
+        assert_eq!(sorted, files);
+
+        let mut order = Builder::new().suffix(".txt").tempfile()?;
+        writeln!(order, "# newest first\n\nhttps://files.catbox.moe/def456.png")?;
+        let order_from = order.path().to_string_lossy().into_owned();
+
+        assert_eq!(order_from_file(&files, &order_from)?, vec![files[1].clone(), files[0].clone()]);
+
+        writeln!(order, "ghi789.gif")?;
+        assert!(order_from_file(&files, &order_from).is_err());
+
+        for command in [
+            vec!["catbox", "album", "reorder", "-u", "1", "--sort", "exif-date", "asd123"],
+            vec!["catbox", "album", "reorder", "-u", "1", "--sort", "name", "-r", "asd123"],
+            vec!["catbox", "album", "reorder", "-u", "1", "--sort", "size", "asd123"],
+        ] {
+            let args = CatboxArgs::parse_from(command);
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                parse_album(album_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        let invalid = vec!["catbox", "album", "reorder", "-u", "1", "asd123"];
+        assert!(CatboxArgs::try_parse_from(invalid).is_err());
+
+        Ok(())
+    }
+}
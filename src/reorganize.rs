@@ -0,0 +1,159 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! New albums made from the files of existing albums: clones, merges and splits
+//!
+//! The original albums are not changed.
+
+use anyhow::Result;
+use catbox::{ album::Split, upload::UploadOptions };
+
+#[cfg(not(test))]
+use catbox::album;
+#[cfg(test)]
+use crate::test::catbox::album;
+
+use crate::{
+    args::{ AlbumClone, AlbumMerge, AlbumSplit, SplitBy },
+    batch,
+};
+
+/// Create a new album with the files, title and description of an album
+pub async fn clone_album(album_clone_args: AlbumClone) -> Result<()> {
+    let info = album::show(&album_clone_args.short).await?;
+
+    let title = album_clone_args.title.unwrap_or(info.title);
+
+    batch::create(
+        &title,
+        &info.description,
+        album_clone_args.user_hash.as_ref(),
+        &batch::remote(&info.files),
+        &UploadOptions::default()
+    ).await?;
+
+    Ok(())
+}
+
+/// Create a new album with the files of several albums, without duplicates
+pub async fn merge_album(album_merge_args: AlbumMerge) -> Result<()> {
+    let mut albums = Vec::new();
+
+    for short in &album_merge_args.shorts {
+        albums.push(album::show(short).await?);
+    }
+
+    let description = album_merge_args.description.unwrap_or_else(|| {
+        albums.first().map(|album| album.description.clone()).unwrap_or_default()
+    });
+
+    let files = album::merge(&albums);
+
+    batch::create(
+        &album_merge_args.into,
+        &description,
+        album_merge_args.user_hash.as_ref(),
+        &batch::remote(&files),
+        &UploadOptions::default()
+    ).await?;
+
+    Ok(())
+}
+
+/// Divide the files of an album into new albums, by extension or number of files
+pub async fn split_album(album_split_args: AlbumSplit) -> Result<()> {
+    let info = album::show(&album_split_args.short).await?;
+
+    let by = match album_split_args.by {
+        SplitBy::Extension => Split::Extension,
+        SplitBy::Count => Split::Count(album_split_args.size.into()),
+    };
+
+    for (label, files) in album::split(&info.files, by) {
+        let title = format!("{} ({label})", info.title);
+
+        println!("{title}: {} files", files.len());
+
+        batch::create(
+            &title,
+            &info.description,
+            album_split_args.user_hash.as_ref(),
+            &batch::remote(&files),
+            &UploadOptions::default()
+        ).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ args::{ CatboxArgs, CatboxCommand }, ledger, parse_album, test };
+    use catbox::{ id::FileId, url::album_short };
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn album_reorganize() -> Result<()> {
+        let _state = test::isolate();
+
+        let files: Vec<FileId> = vec![
+            "a1.jpg".parse()?,
+            "b2.png".parse()?,
+            "c3.JPG".parse()?,
+            "d4".parse()?
+        ];
+
+        let by_extension = album::split(&files, Split::Extension);
+        let labels: Vec<_> = by_extension.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, vec!["jpg", "png", "none"]);
+        assert_eq!(by_extension[0].1, vec![files[0].clone(), files[2].clone()]);
+
+        let by_count = album::split(&files, Split::Count(3));
+        assert_eq!(by_count[1], ("2/2".to_string(), vec![files[3].clone()]));
+
+        let first = album::show(&"asd123".parse()?).await?;
+        let mut second = first.clone();
+        second.files.push("ghi789.gif".parse()?);
+        assert_eq!(album::merge(&[first, second]).len(), 3);
+
+        // every new album is recorded, the mock always creates album 123435
+        let created = album_short("123435")?;
+
+        for (command, title, files) in [
+            (vec!["clone", "-u", "1", "-t", "Copy", "asd123"], "Copy", 2),
+            (vec!["merge", "-u", "1", "--into", "All", "asd123", "qwe456"], "All", 2),
+            (vec!["split", "--by", "count", "--size", "1", "asd123"], "My album (2/2)", 1),
+            (vec!["split", "--by", "extension", "asd123"], "My album (png)", 1),
+        ] {
+            let args = CatboxArgs::parse_from([vec!["catbox", "album"], command].concat());
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                parse_album(album_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+
+            let recorded = ledger::find_album(&created)?.unwrap();
+            assert_eq!((recorded.title.as_str(), recorded.files.len()), (title, files));
+        }
+
+        let invalid = vec!["catbox", "album", "merge", "-u", "1", "--into", "All", "asd123"];
+        assert!(CatboxArgs::try_parse_from(invalid).is_err());
+
+        let invalid = vec![
+            "catbox",
+            "album",
+            "split",
+            "-u",
+            "1",
+            "--by",
+            "count",
+            "--size",
+            "0",
+            "asd123"
+        ];
+        assert!(CatboxArgs::try_parse_from(invalid).is_err());
+
+        Ok(())
+    }
+}
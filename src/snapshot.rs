@@ -0,0 +1,431 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Local snapshots of albums, for restoring them after accidental changes
+//!
+//! A snapshot is either a JSON file with the title, description and ordered files of an album,
+//! or a tar archive with that JSON as `album.json` followed by the contents of the files.
+
+use anyhow::{ Context, Result, anyhow, bail };
+use catbox::{
+    album::{ AlbumFile, AlbumInfo },
+    download::DownloadOptions,
+    id::{ AlbumShort, FileId, UserHash },
+    upload::UploadOptions,
+    url::file_url,
+};
+use futures::stream::{ self, StreamExt };
+use serde::{ Deserialize, Serialize };
+use std::{
+    fs::{ self, File },
+    io::{ self, BufReader, BufWriter, Read, Write },
+    path::Path,
+    time::{ SystemTime, UNIX_EPOCH },
+};
+
+#[cfg(not(test))]
+use catbox::{ album, download };
+#[cfg(test)]
+use crate::test::catbox::{ album, download };
+
+use crate::{
+    args::{ self, AlbumBackup, AlbumRestore },
+    batch,
+    record_album,
+    record_uploads,
+};
+
+/// Name of the album description inside tar snapshots
+pub const ALBUM_JSON: &str = "album.json";
+
+const BLOCK_SIZE: usize = 512;
+const MAX_NAME_LEN: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(flatten)]
+    pub album: AlbumInfo,
+    /// Unix timestamp of the backup
+    pub created: u64,
+}
+
+/// Write a snapshot as JSON
+pub fn write_json(path: &Path, snapshot: &Snapshot) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(snapshot)?)?;
+
+    Ok(())
+}
+
+/// Write a snapshot as tar archive, together with the contents of its files
+///
+/// # Arguments
+///
+/// * `path` - Path of the archive
+/// * `snapshot` - Album to save
+/// * `files` - Names in the archive and paths of the local copies of the files
+pub fn write_tar(path: &Path, snapshot: &Snapshot, files: &[(String, String)]) -> Result<()> {
+    let mut output = BufWriter::new(File::create(path)?);
+    let json = serde_json::to_vec_pretty(snapshot)?;
+
+    write_entry(&mut output, ALBUM_JSON, json.len() as u64, snapshot.created, &mut &json[..])?;
+
+    for (name, file_path) in files {
+        let mut file = File::open(file_path)?;
+        let size = file.metadata()?.len();
+
+        write_entry(&mut output, name, size, snapshot.created, &mut file)?;
+    }
+
+    // the end of an archive is marked by two empty blocks
+    output.write_all(&[0; BLOCK_SIZE * 2])?;
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Read a snapshot, extracting the files of tar archives into a directory
+///
+/// Returns the snapshot and the paths of the extracted files by name
+///
+/// # Arguments
+///
+/// * `path` - Path of the JSON file or tar archive
+/// * `dir` - Directory to extract files into, created if needed
+pub fn read(path: &Path, dir: &Path) -> Result<(Snapshot, Vec<(String, String)>)> {
+    let mut input = BufReader::new(File::open(path)?);
+    let mut header = [0; BLOCK_SIZE];
+
+    let read = input.read(&mut header)?;
+
+    if header[..read].trim_ascii_start().starts_with(b"{") {
+        let mut json = header[..read].to_vec();
+        input.read_to_end(&mut json)?;
+
+        let snapshot = serde_json::from_slice(&json)
+            .with_context(|| path.display().to_string())?;
+
+        return Ok((snapshot, Vec::new()));
+    }
+
+    input.read_exact(&mut header[read..])?;
+
+    let mut snapshot = None;
+    let mut files = Vec::new();
+
+    while header.iter().any(|&byte| byte != 0) {
+        let name = String::from_utf8_lossy(field(&header[..MAX_NAME_LEN])).into_owned();
+        let size = octal(&header[124..136])?;
+        let padding = (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64;
+
+        let mut entry = (&mut input).take(size);
+
+        if name == ALBUM_JSON {
+            let mut json = Vec::new();
+            entry.read_to_end(&mut json)?;
+            snapshot = Some(serde_json::from_slice(&json)?);
+        } else if Path::new(&name).file_name() == Some(name.as_ref()) {
+            fs::create_dir_all(dir)?;
+
+            let file_path = dir.join(&name);
+            io::copy(&mut entry, &mut File::create(&file_path)?)?;
+
+            files.push((name, file_path.to_string_lossy().into_owned()));
+        } else {
+            bail!("{name:?} in {} is not a plain file name", path.display());
+        }
+
+        io::copy(&mut (&mut input).take(padding), &mut io::sink())?;
+        input.read_exact(&mut header)?;
+    }
+
+    let Some(snapshot) = snapshot else {
+        bail!("{} does not contain {ALBUM_JSON}", path.display());
+    };
+
+    Ok((snapshot, files))
+}
+
+/// Write a ustar header followed by the contents, padded to full blocks
+fn write_entry<W: Write, R: Read>(
+    output: &mut W,
+    name: &str,
+    size: u64,
+    mtime: u64,
+    contents: &mut R
+) -> Result<()> {
+    if name.len() > MAX_NAME_LEN {
+        bail!("{name} is too long for a tar archive");
+    }
+
+    let mut header = [0; BLOCK_SIZE];
+
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes());
+    header[136..148].copy_from_slice(format!("{mtime:011o}\0").as_bytes());
+    header[156] = b'0';
+    header[257..265].copy_from_slice(b"ustar\x0000");
+
+    // the checksum is calculated with its own field filled with spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+    output.write_all(&header)?;
+
+    if io::copy(contents, output)? != size {
+        bail!("{name} changed while it was archived");
+    }
+
+    let padding = (BLOCK_SIZE - usize::try_from(size % BLOCK_SIZE as u64)?) % BLOCK_SIZE;
+    output.write_all(&vec![0; padding])?;
+
+    Ok(())
+}
+
+/// Text of a header field, which ends at the first null byte
+fn field(bytes: &[u8]) -> &[u8] {
+    bytes.split(|&byte| byte == 0).next().unwrap_or_default()
+}
+
+/// Parse a number stored as octal text
+fn octal(bytes: &[u8]) -> Result<u64> {
+    let text = String::from_utf8_lossy(field(bytes));
+
+    u64::from_str_radix(text.trim(), 8).with_context(|| format!("Invalid tar header: {text:?}"))
+}
+
+/// Save albums as snapshots, optionally including their files
+pub async fn backup_album(album_backup_args: AlbumBackup) -> Result<()> {
+    let output = Path::new(&album_backup_args.output);
+    let created = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    std::fs::create_dir_all(output)?;
+
+    for short in &album_backup_args.shorts {
+        let snapshot = Snapshot { album: album::show(short).await?, created };
+        let name = format!("{short}-{created}");
+
+        if !album_backup_args.with_files {
+            let path = output.join(format!("{name}.json"));
+            write_json(&path, &snapshot)?;
+
+            println!("{} ({} files)", path.display(), snapshot.album.files.len());
+            continue;
+        }
+
+        // the files are downloaded next to the archive first, and removed once it is written
+        let staging = output.join(format!(".{name}"));
+        let staging_dir = staging.to_string_lossy().into_owned();
+        let options = DownloadOptions { overwrite: true, ..Default::default() };
+
+        let downloads: Vec<_> = stream::iter(&snapshot.album.files)
+            .map(|file| download::file_with(file_url(file), staging_dir.clone(), &options))
+            .buffered(10)
+            .collect().await;
+
+        let path = output.join(format!("{name}.tar"));
+
+        let res = snapshot.album.files
+            .iter()
+            .zip(downloads)
+            .map(|(file, downloaded)| Ok((file.to_string(), downloaded?.path)))
+            .collect::<Result<Vec<_>>>()
+            .and_then(|files| {
+                write_tar(&path, &snapshot, &files)?;
+                Ok(files.len())
+            });
+
+        // the staging directory is removed before a failed download or write is reported
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+
+        println!("{} ({} files)", path.display(), res?);
+    }
+
+    Ok(())
+}
+
+/// Restore an album from a snapshot, uploading files which were deleted from Catbox again
+pub async fn restore_album(album_restore_args: AlbumRestore) -> Result<()> {
+    let user_hash = args::required(album_restore_args.user_hash)?;
+    let options = UploadOptions::default();
+    let staging = std::env::temp_dir().join(format!("catbox-restore-{}", std::process::id()));
+
+    let (snapshot, extracted) = read(Path::new(&album_restore_args.snapshot), &staging)?;
+    let info = snapshot.album;
+
+    let res = async {
+        let files = snapshot_files(&info.files, &extracted).await?;
+        restore_files(album_restore_args.short, info, &user_hash, &files, &options).await
+    }.await;
+
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+
+    res
+}
+
+/// Files of a snapshot, with files which were deleted from Catbox taken from the snapshot
+///
+/// Fails if a file cannot be checked, so files are not uploaded again or skipped
+/// because of a temporary error.
+///
+/// # Arguments
+///
+/// * `files` - Files of the album in the snapshot
+/// * `extracted` - Names and paths of the files extracted from the snapshot
+async fn snapshot_files(
+    files: &[FileId],
+    extracted: &[(String, String)]
+) -> Result<Vec<AlbumFile>> {
+    let available: Vec<_> = stream::iter(files)
+        .map(|file| download::exists(file_url(file)))
+        .buffered(10)
+        .collect().await;
+
+    let mut restored = Vec::new();
+
+    for (file, available) in files.iter().zip(available) {
+        let available = available.map_err(|err| {
+            anyhow!("Checking whether {file} still exists failed: {err}")
+        })?;
+
+        if available {
+            restored.push(AlbumFile::Remote(file.clone()));
+        } else if let Some((_, path)) = extracted.iter().find(|(name, _)| name == file.as_str()) {
+            restored.push(AlbumFile::Local(path.clone()));
+        } else {
+            println!("Skipping {file}: it was deleted and is not in the snapshot");
+        }
+    }
+
+    Ok(restored)
+}
+
+/// Create an album from restored files, or replace the files of an existing album with them
+async fn restore_files(
+    short: Option<AlbumShort>,
+    info: AlbumInfo,
+    user_hash: &UserHash,
+    files: &[AlbumFile],
+    options: &UploadOptions
+) -> Result<()> {
+    let Some(short) = short else {
+        batch::create(&info.title, &info.description, Some(user_hash), files, options).await?;
+
+        return Ok(());
+    };
+
+    // only the local files are uploaded, the album gets all files with one edit
+    let (files, uploaded) = album::upload_files(files, Some(user_hash), options).await?;
+    record_uploads(&uploaded, options).await;
+
+    let res = batch::edit(&short, &info.title, &info.description, user_hash, &files).await?;
+
+    record_album(short.as_str(), &info.title, &files);
+
+    println!("{res}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ args::{ CatboxArgs, CatboxCommand }, parse_album, test };
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn album_backup() -> Result<()> {
+        let _state = test::isolate();
+
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().to_str().unwrap();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "album", "backup", "-o", output, "asd123", "qwe456"]
+        );
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            parse_album(album_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let backups = std::fs::read_dir(dir.path())?.count();
+        assert_eq!(backups, 2);
+
+        // a failed download leaves neither an archive nor the downloaded files behind
+        let failed = tempfile::tempdir()?;
+        let failed_output = failed.path().to_str().unwrap();
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "album", "backup", "--with-files", "-o", failed_output, "broken1"]
+        );
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            assert!(parse_album(album_args).await.is_err());
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        assert_eq!(std::fs::read_dir(failed.path())?.count(), 0);
+
+        let contents = dir.path().join("gone1.txt");
+        std::fs::write(&contents, "restored")?;
+
+        let snapshot = Snapshot {
+            album: album::show(&"asd123".parse()?).await?,
+            created: 1_700_000_000,
+        };
+        let mut gone = snapshot.clone();
+        gone.album.files.push("gone1.txt".parse()?);
+        gone.album.files.push("gone2.txt".parse()?);
+
+        let tar = dir.path().join("asd123.tar");
+        let files = vec![("gone1.txt".to_string(), contents.to_string_lossy().into_owned())];
+        write_tar(&tar, &gone, &files)?;
+
+        let extracted = dir.path().join("extracted");
+        let (restored, files) = read(&tar, &extracted)?;
+
+        assert_eq!(restored, gone);
+        assert_eq!(files.len(), 1);
+        assert_eq!(std::fs::read_to_string(&files[0].1)?, "restored");
+        assert_eq!(std::fs::metadata(&tar)?.len() % 512, 0);
+
+        let json = dir.path().join("asd123.json");
+        write_json(&json, &snapshot)?;
+        assert_eq!(read(&json, &extracted)?, (snapshot, Vec::new()));
+
+        let tar = tar.to_str().unwrap();
+        let json = json.to_str().unwrap();
+
+        for command in [
+            vec!["catbox", "album", "restore", "-u", "1", tar],
+            vec!["catbox", "album", "restore", "-u", "1", "-a", "asd123", tar],
+            vec!["catbox", "album", "restore", "-u", "1", json],
+        ] {
+            let args = CatboxArgs::parse_from(command);
+
+            if let CatboxCommand::Album(album_args) = args.command {
+                parse_album(album_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        // deleted files are uploaded again, other errors abort the restore
+        let restored = snapshot_files(&gone.album.files, &files).await?;
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored[2], AlbumFile::Local(files[0].1.clone()));
+
+        let broken = vec!["abc123.jpg".parse()?, "broken1.txt".parse()?];
+        assert!(snapshot_files(&broken, &files).await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Synchronization of an album with a local directory
+//!
+//! Title and description of the album are read from a sidecar file in the directory:
+//!
+//! ```json
+//! { "title": "Portfolio", "description": "Selected work" }
+//! ```
+
+use anyhow::{ Context, Result };
+use catbox::{
+    album::{ AlbumFile, Changes },
+    id::{ AlbumShort, FileId },
+    upload::UploadOptions,
+    url::file_id,
+};
+use serde::Deserialize;
+use std::{ fs, io::ErrorKind, path::Path };
+
+#[cfg(not(test))]
+use catbox::{ album, file };
+#[cfg(test)]
+use crate::test::catbox::{ album, file };
+
+use crate::{
+    args::{ self, AlbumSync },
+    batch::{ self, ALBUM_BATCH_SIZE, DELETE_BATCH_SIZE },
+    delete::known_albums,
+    ledger,
+    record_album,
+};
+
+/// Name of the sidecar file, hidden files are not synchronized
+pub const SIDECAR: &str = ".album.json";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct Sidecar {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Read the sidecar file of a directory, if there is one
+pub fn sidecar(dir: &str) -> Result<Option<Sidecar>> {
+    let path = Path::new(dir).join(SIDECAR);
+
+    match fs::read_to_string(&path) {
+        Ok(sidecar) =>
+            Ok(Some(serde_json::from_str(&sidecar).with_context(|| path.display().to_string())?)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// List the files in a directory which belong into the album, sorted by name
+///
+/// Hidden files and subdirectories are skipped.
+pub fn local_files(dir: &str) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if entry.file_name().to_string_lossy().starts_with('.') || !entry.file_type()?.is_file() {
+            continue;
+        }
+
+        files.push(entry.path().to_string_lossy().into_owned());
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// Make an album mirror a directory: add new files, remove deleted ones and update its details
+pub async fn sync_album(album_sync_args: AlbumSync) -> Result<()> {
+    let short = album_sync_args.short;
+    let user_hash = args::required(album_sync_args.user_hash)?;
+    let options = UploadOptions::default();
+
+    let current = album::show(&short).await?;
+    let sidecar = sidecar(&album_sync_args.dir)?.unwrap_or_default();
+
+    let mut kept = Vec::new();
+    let mut new = Vec::new();
+
+    for path in local_files(&album_sync_args.dir)? {
+        match ledger::find_upload(&path).await? {
+            Some(entry) if current.files.contains(&file_id(&entry.url)?) => {
+                kept.push(file_id(&entry.url)?);
+            }
+            Some(entry) => new.push(AlbumFile::Remote(file_id(&entry.url)?)),
+            None => new.push(AlbumFile::Local(path)),
+        }
+    }
+
+    let removed: Vec<_> = current.files
+        .iter()
+        .filter(|file| !kept.contains(file))
+        .cloned()
+        .collect();
+
+    let title = sidecar.title.unwrap_or_else(|| current.title.clone());
+    let description = sidecar.description.unwrap_or_else(|| current.description.clone());
+    let details_changed = title != current.title || description != current.description;
+
+    if new.is_empty() && removed.is_empty() && !details_changed {
+        println!("{} is up to date", current.url());
+        return Ok(());
+    }
+
+    let details = Changes {
+        title: (title != current.title).then(|| (current.title.clone(), title.clone())),
+        description: (description != current.description).then(|| {
+            (current.description.clone(), description.clone())
+        }),
+        ..Default::default()
+    };
+
+    if details_changed {
+        println!("{details}");
+    }
+
+    for file in &new {
+        match file {
+            AlbumFile::Remote(id) => println!("+ {id}"),
+            AlbumFile::Local(path) => println!("+ {path}"),
+        }
+    }
+
+    for file in &removed {
+        println!("- {file}");
+    }
+
+    let orphans = if album_sync_args.delete_orphans {
+        sync_orphans(&short, &removed).await?
+    } else {
+        Vec::new()
+    };
+
+    let mut files = kept;
+
+    if !new.is_empty() {
+        let (res, ids) = batch::add(&short, &user_hash, &new, &options).await?;
+
+        println!("{res}");
+        files.extend(ids);
+    }
+
+    for batch in removed.chunks(ALBUM_BATCH_SIZE) {
+        println!("{}", album::remove_files(&short, &user_hash, batch).await?);
+    }
+
+    for batch in orphans.chunks(DELETE_BATCH_SIZE) {
+        println!("{}", file::delete(batch, &user_hash).await?);
+    }
+
+    if details_changed {
+        println!("{}", batch::edit(&short, &title, &description, &user_hash, &files).await?);
+    }
+
+    record_album(short.as_str(), &title, &files);
+
+    Ok(())
+}
+
+/// Files removed by a sync which can be deleted from Catbox
+///
+/// Only files uploaded with this tool are deleted, and only if no other known album shows them.
+///
+/// # Arguments
+///
+/// * `short` - ID of the synced album
+/// * `removed` - Files removed from the album
+async fn sync_orphans(short: &AlbumShort, removed: &[FileId]) -> Result<Vec<FileId>> {
+    let others = known_albums(Some(short)).await?;
+    let mut orphans = Vec::new();
+
+    for file in removed {
+        if ledger::find_id(file.as_str())?.is_none() {
+            println!("Keeping {file}, it was not uploaded with this tool");
+        } else if let Some(album) = others.iter().find(|album| album.files.contains(file)) {
+            println!("Keeping {file}, it is still in {}", album.url());
+        } else {
+            orphans.push(file.clone());
+        }
+    }
+
+    Ok(orphans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ args::{ CatboxArgs, CatboxCommand }, parse_album, test };
+    use catbox::url::album_short;
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn album_sync() -> Result<()> {
+        let _state = test::isolate();
+
+        let dir = tempfile::tempdir()?;
+
+        std::fs::write(dir.path().join("one.txt"), "one")?;
+        std::fs::write(dir.path().join("two.txt"), "two")?;
+        std::fs::write(dir.path().join(".hidden"), "skipped")?;
+        std::fs::write(dir.path().join(SIDECAR), r#"{ "title": "Portfolio" }"#)?;
+
+        let path = dir.path().to_str().unwrap();
+
+        assert_eq!(local_files(path)?.len(), 2);
+        let title = sidecar(path)?.and_then(|sidecar| sidecar.title);
+        assert_eq!(title, Some("Portfolio".to_string()));
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "album", "sync", "-u", "1", "-a", "asd123", "--delete-orphans", path]
+        );
+
+        if let CatboxCommand::Album(album_args) = args.command {
+            parse_album(album_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        std::fs::write(dir.path().join(SIDECAR), "{ title: ")?;
+        assert!(sidecar(path).is_err());
+
+        // only files uploaded with this tool are deleted as orphans
+        let uploaded = dir.path().join("one.txt");
+        let link = "https://files.catbox.moe/orphan1.txt";
+        ledger::record(uploaded.to_str().unwrap(), link, &UploadOptions::default()).await?;
+
+        let removed = vec!["orphan1.txt".parse()?, "never1.png".parse()?];
+        let orphans = sync_orphans(&album_short("asd123")?, &removed).await?;
+        assert_eq!(orphans, vec![file_id(link)?]);
+
+        Ok(())
+    }
+}
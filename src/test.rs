@@ -1,123 +1,272 @@
+use tempfile::TempDir;
+
+use crate::{ ledger, trash };
+
+/// Give the running test its own ledger and trash, removed together with the returned directory
+///
+/// Tests run in parallel on their own threads, so they cannot see each other's records.
+pub fn isolate() -> TempDir {
+    let dir = tempfile::tempdir().expect("creating a directory for the test failed");
+
+    ledger::TEST_PATH.set(Some(dir.path().join("ledger.jsonl")));
+    trash::TEST_DIR.set(Some(dir.path().join("trash")));
+
+    dir
+}
+
 #[allow(clippy::unused_async)]
 pub mod catbox {
     pub mod album {
         use anyhow::Result;
+        use catbox::{
+            album::{ AlbumFile, AlbumInfo, AlbumUpload, Uploaded },
+            id::{ AlbumShort, FileId, UserHash },
+            upload::UploadOptions,
+            url::{ file_id, file_url },
+        };
+        use std::path::Path;
+
+        pub use catbox::album::{ merge, split };
 
-        pub async fn create<S: Into<String>>(
+        pub async fn create_from_files<S: Into<String>>(
             _title: S,
             _desc: S,
-            _user_hash: S,
-            _files: Vec<S>
+            _user_hash: Option<&UserHash>,
+            files: &[AlbumFile],
+            _options: &UploadOptions
+        ) -> Result<AlbumUpload> {
+            Ok(AlbumUpload {
+                response: "https://catbox.moe/c/123435".to_string(),
+                uploaded: uploads(files)?,
+            })
+        }
+
+        pub async fn add_from_files(
+            short: &AlbumShort,
+            _user_hash: &UserHash,
+            files: &[AlbumFile],
+            _options: &UploadOptions
+        ) -> Result<AlbumUpload> {
+            Ok(AlbumUpload {
+                response: format!("https://catbox.moe/c/{short}"),
+                uploaded: uploads(files)?,
+            })
+        }
+
+        pub async fn upload_files(
+            files: &[AlbumFile],
+            _user_hash: Option<&UserHash>,
+            _options: &UploadOptions
+        ) -> Result<(Vec<FileId>, Vec<Uploaded>)> {
+            let uploaded = uploads(files)?;
+            let mut ids = uploaded.iter().map(|uploaded| uploaded.id.clone());
+
+            let files = files
+                .iter()
+                .map(|file| match file {
+                    AlbumFile::Remote(id) => id.clone(),
+                    AlbumFile::Local(_) => ids.next().unwrap(),
+                })
+                .collect();
+
+            Ok((files, uploaded))
+        }
+
+        fn uploads(files: &[AlbumFile]) -> Result<Vec<Uploaded>> {
+            let mut uploaded = Vec::new();
+
+            for file in files {
+                if let AlbumFile::Local(path) = file {
+                    let name = Path::new(path).file_name().unwrap().to_string_lossy();
+                    let link = file_url(name.as_ref());
+
+                    uploaded.push(Uploaded { path: path.clone(), id: file_id(&link)?, link });
+                }
+            }
+
+            Ok(uploaded)
+        }
+
+        pub async fn add_files(
+            short: &AlbumShort,
+            _user_hash: &UserHash,
+            _files: &[FileId]
         ) -> Result<String> {
-            Ok("https://catbox.moe/c/123435".to_string())
+            Ok(format!("https://catbox.moe/c/{short}"))
         }
 
-        pub async fn delete<S: Into<String>>(_short: S, _user_hash: S) -> Result<String> {
+        pub async fn delete(_short: &AlbumShort, _user_hash: &UserHash) -> Result<String> {
             Ok(String::new())
         }
 
         pub async fn edit<S: Into<String>>(
-            short: S,
+            short: &AlbumShort,
             _title: S,
             _desc: S,
-            _user_hash: S,
-            _files: Vec<S>
+            _user_hash: &UserHash,
+            _files: &[FileId]
+        ) -> Result<String> {
+            Ok(format!("https://catbox.moe/c/{short}"))
+        }
+
+        pub async fn remove_files(
+            short: &AlbumShort,
+            _user_hash: &UserHash,
+            _files: &[FileId]
         ) -> Result<String> {
-            let short = short.into();
+            Ok(format!("https://catbox.moe/c/{short}"))
+        }
+
+        pub async fn show(short: &AlbumShort) -> Result<AlbumInfo> {
+            let mut files = vec!["abc123.jpg".parse()?, "def456.png".parse()?];
+
+            // albums with files which fail to download
+            if short.as_str().contains("broken") {
+                files.push("broken1.txt".parse()?);
+            }
 
+            Ok(AlbumInfo {
+                short: short.clone(),
+                title: "My album".to_string(),
+                description: "An excellent album".to_string(),
+                files,
+            })
+        }
+    }
+
+    pub mod file {
+        use anyhow::{ Result, bail };
+        use catbox::{ id::{ FileId, UserHash }, upload::Prepared };
+        use url::Url;
+
+        pub async fn upload_prepared(
+            prepared: Prepared,
+            _user_hash: Option<&UserHash>
+        ) -> Result<String> {
             Ok(
-                if short.is_empty() {
-                    "No album found for specified user.".to_string()
-                } else {
-                    format!("https://catbox.moe/c/{short}")
-                }
+                format!(
+                    "https://files.catbox.moe/{}{}",
+                    prepared.file_name,
+                    if prepared.key.is_some() { "#key" } else { "" }
+                )
             )
         }
 
-        pub async fn add_files<S: Into<String>>(
-            short: S,
-            _user_hash: S,
-            _files: Vec<S>
+        pub async fn share_page<S: Into<String>>(
+            link: S,
+            file_name: S,
+            _user_hash: Option<&UserHash>
         ) -> Result<String> {
-            let short = short.into();
+            let link = link.into();
 
-            Ok(
-                if short.is_empty() {
-                    "No album found for specified user.".to_string()
-                } else {
-                    format!("https://catbox.moe/c/{short}")
-                }
-            )
+            let Some((_, key)) = link.split_once('#') else {
+                bail!("{link} does not contain an encryption key");
+            };
+
+            Ok(format!("https://catbox.moe/{}.html#{key}", file_name.into()))
         }
 
-        pub async fn remove_files<S: Into<String>>(
-            short: S,
-            _user_hash: S,
-            _files: Vec<S>
+        pub async fn from_url<S: Into<String>>(
+            url: S,
+            _user_hash: Option<&UserHash>
         ) -> Result<String> {
-            let short = short.into();
+            let url = url.into();
+
+            Url::parse(&url)?;
 
+            Ok(format!("https://catbox.moe/file.{}", url.split('.').next_back().unwrap()))
+        }
+
+        pub async fn delete(files: &[FileId], _user_hash: &UserHash) -> Result<String> {
             Ok(
-                if short.is_empty() {
-                    "No album found for specified user.".to_string()
+                if files.is_empty() {
+                    "File doesn't exist?".to_string()
                 } else {
-                    format!("https://catbox.moe/c/{short}")
+                    "Files succesfully deleted.".to_string()
                 }
             )
         }
     }
 
-    pub mod file {
-        use anyhow::Result;
-        use std::fs::File;
+    pub mod download {
+        use anyhow::{ Result, bail };
+        use catbox::download::{ DownloadOptions, Downloaded, Peek };
         use url::Url;
 
-        pub async fn from_file<S: Into<String>>(file_path: S, _user_hash: S) -> Result<String> {
-            let file_path = file_path.into();
+        pub use catbox::download::url;
 
-            File::open(&file_path)?;
+        pub async fn peek<S: Into<String>>(url: S, _bytes: u64) -> Result<Peek> {
+            let url = url.into();
+
+            if url.contains("gone") {
+                bail!("404 Not Found for url ({url})");
+            }
 
-            Ok(format!("https://catbox.moe/file.{}", file_path.split('.').next_back().unwrap()))
+            Ok(Peek {
+                size: Some(url.len() as u64),
+                modified: (!url.ends_with(".png")).then_some(1_700_000_000),
+                head: Vec::new(),
+            })
         }
 
-        pub async fn from_url<S: Into<String>>(url: S, _user_hash: S) -> Result<String> {
+        pub async fn exists<S: Into<String>>(url: S) -> Result<bool> {
             let url = url.into();
 
-            Url::parse(&url)?;
+            if url.contains("broken") {
+                bail!("500 Internal Server Error for url ({url})");
+            }
 
-            Ok(format!("https://catbox.moe/file.{}", url.split('.').next_back().unwrap()))
+            Ok(!url.contains("gone"))
         }
 
-        pub async fn delete<S: Into<String>>(files: Vec<S>, _user_hash: S) -> Result<String> {
-            let valid = files
-                .into_iter()
-                .map(Into::into)
-                .all(|file| !file.is_empty());
+        pub async fn file_with<S: Into<String>>(
+            url: S,
+            dir: S,
+            options: &DownloadOptions
+        ) -> Result<Downloaded> {
+            let url = Url::parse(&url.into())?;
 
-            Ok(
-                if valid {
-                    "Files succesfully deleted.".to_string()
-                } else {
-                    "File doesn't exist?".to_string()
-                }
-            )
+            let Some(mut name) = url.path_segments().and_then(|mut parts| parts.next_back()) else {
+                bail!("{url} does not point to a file");
+            };
+
+            // an interrupted download leaves its part file behind
+            if name.contains("broken") {
+                let dir = dir.into();
+                std::fs::create_dir_all(&dir)?;
+                std::fs::write(format!("{dir}/{name}.part"), "partial")?;
+                bail!("Downloading {url} failed");
+            }
+
+            if url.fragment().is_some() {
+                name = name.trim_end_matches(".enc");
+            }
+
+            if options.decompress {
+                name = name.trim_end_matches(".zst").trim_end_matches(".gz");
+            }
+
+            let decoded = url.fragment().is_some() || options.decompress;
+
+            Ok(Downloaded {
+                path: format!("{}/{}", dir.into(), options.name.as_deref().unwrap_or(name)),
+                skipped: false,
+                resumed: 0,
+                decoded,
+            })
         }
     }
 
     pub mod litter {
         use anyhow::Result;
-        use std::fs::File;
-
-        pub async fn upload<S: Into<String>>(file_path: S, time: u8) -> Result<String> {
-            let file_path = file_path.into();
+        use catbox::upload::Prepared;
 
+        pub async fn upload_prepared(prepared: Prepared, time: u8) -> Result<String> {
             if ![1, 12, 24, 72].contains(&time) {
                 return Ok("Invalid time".to_string());
             }
 
-            File::open(&file_path)?;
-
-            Ok(format!("https://catbox.moe/file.{}", file_path.split(',').next_back().unwrap()))
+            Ok(format!("https://litter.catbox.moe/{}", prepared.file_name))
         }
     }
 }
@@ -0,0 +1,343 @@
+#![allow(clippy::missing_errors_doc)]
+
+//! Local copies of deleted files, so deletes on Catbox can be undone
+//!
+//! Every file in the trash has a directory named after its Catbox ID,
+//! with the downloaded file and an `item.json` recording the albums it was in.
+//!
+//! The trash is stored at `$CATBOX_TRASH`, or `catbox/trash` in the user's data directory.
+
+use anyhow::{ Result, bail };
+use catbox::{
+    album::AlbumInfo,
+    download::DownloadOptions,
+    dry_run,
+    id::{ AlbumShort, FileId },
+    upload::{ self, UploadOptions },
+    url::{ album_url, file_id, file_url },
+};
+use serde::{ Deserialize, Serialize };
+use std::{ fs, io::ErrorKind, path::PathBuf, time::{ SystemTime, UNIX_EPOCH } };
+
+#[cfg(not(test))]
+use catbox::{ album, download, file };
+#[cfg(test)]
+use crate::test::catbox::{ album, download, file };
+
+use crate::{
+    args::{ self, Format, Trash, TrashCommand, TrashList, TrashPurge, TrashRestore },
+    ledger,
+    record_album,
+};
+
+/// Name of the description of a file in its directory
+pub const ITEM_JSON: &str = "item.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    /// Catbox ID of the deleted file
+    pub id: FileId,
+    /// Path of the downloaded copy
+    pub path: String,
+    /// Albums which showed the file when it was deleted
+    pub albums: Vec<AlbumShort>,
+    /// Unix timestamp of the deletion
+    pub deleted: u64,
+}
+
+#[cfg(not(test))]
+pub fn dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("CATBOX_TRASH") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    Ok(
+        dirs
+            ::data_dir()
+            .ok_or_else(|| anyhow::anyhow!("Cannot determine the data directory for the trash"))?
+            .join("catbox")
+            .join("trash")
+    )
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Trash of the running test, see [`crate::test::isolate`]
+    pub static TEST_DIR: std::cell::RefCell<Option<PathBuf>> = const {
+        std::cell::RefCell::new(None)
+    };
+}
+
+#[cfg(test)]
+pub fn dir() -> Result<PathBuf> {
+    TEST_DIR.with_borrow(Clone::clone).ok_or_else(|| anyhow::anyhow!("The test has no trash"))
+}
+
+/// Directory for the copy of a file
+pub fn item_dir(id: &FileId) -> Result<PathBuf> {
+    Ok(dir()?.join(id.as_str()))
+}
+
+/// Record a file which was downloaded into its directory
+pub fn add(item: &Item) -> Result<()> {
+    let dir = item_dir(&item.id)?;
+
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(ITEM_JSON), serde_json::to_string_pretty(item)?)?;
+
+    Ok(())
+}
+
+/// All files in the trash, oldest deletion first
+///
+/// Directories without a valid description are skipped.
+pub fn items() -> Result<Vec<Item>> {
+    let entries = match fs::read_dir(dir()?) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(err) => {
+            return Err(err.into());
+        }
+    };
+
+    let mut items: Vec<Item> = Vec::new();
+
+    for entry in entries {
+        let Ok(item) = fs::read_to_string(entry?.path().join(ITEM_JSON)) else {
+            continue;
+        };
+
+        items.extend(serde_json::from_str(&item).ok());
+    }
+
+    items.sort_by_key(|item| item.deleted);
+
+    Ok(items)
+}
+
+/// Find a file in the trash by its Catbox ID
+pub fn find(id: &FileId) -> Result<Option<Item>> {
+    Ok(items()?.into_iter().find(|item| item.id == *id))
+}
+
+/// Remove a file from the trash for good
+///
+/// Keeps the file during dry runs.
+pub fn remove(id: &FileId) -> Result<()> {
+    if dry_run::enabled() {
+        return Ok(());
+    }
+
+    match fs::remove_dir_all(item_dir(id)?) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Run a trash subcommand
+pub async fn parse_trash(trash_args: Trash) -> Result<()> {
+    match trash_args.trash_command {
+        TrashCommand::List(sub_args) => list_trash(sub_args),
+        TrashCommand::Restore(sub_args) => restore_from_trash(sub_args).await,
+        TrashCommand::Purge(sub_args) => purge_trash(sub_args),
+    }
+}
+
+fn list_trash(trash_list_args: TrashList) -> Result<()> {
+    let items = items()?;
+
+    if trash_list_args.format == Format::Json {
+        println!("{}", serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+
+    if items.is_empty() {
+        println!("The trash is empty");
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    for item in items {
+        let days = now.saturating_sub(item.deleted) / 86_400;
+        let albums: Vec<_> = item.albums.iter().map(album_url).collect();
+
+        match days {
+            0 => print!("{} (deleted today)", item.id),
+            1 => print!("{} (deleted yesterday)", item.id),
+            _ => print!("{} (deleted {days} days ago)", item.id),
+        }
+
+        if albums.is_empty() {
+            println!();
+        } else {
+            println!(" in {}", albums.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+async fn restore_from_trash(trash_restore_args: TrashRestore) -> Result<()> {
+    let user_hash = args::required(trash_restore_args.user_hash)?;
+
+    for id in &trash_restore_args.files {
+        let Some(item) = find(id)? else {
+            bail!("{id} is not in the trash");
+        };
+
+        let prepared = upload::prepare(&item.path, &UploadOptions::default()).await?;
+        let link = file::upload_prepared(prepared, Some(&user_hash)).await?;
+        let new_id = file_id(&link)?;
+
+        println!("{id} -> {link}");
+
+        for short in &item.albums {
+            let added = album::add_files(short, &user_hash, std::slice::from_ref(&new_id)).await;
+
+            if let Err(err) = added {
+                println!("Adding {new_id} to {} failed: {err}", album_url(short));
+                continue;
+            }
+
+            println!("Added {new_id} to {}", album_url(short));
+
+            if let Some(mut entry) = ledger::find_album(short)? {
+                entry.files.retain(|file| file != id);
+                entry.files.push(new_id.clone());
+                record_album(short.as_str(), &entry.title, &entry.files);
+            }
+        }
+
+        remove(id)?;
+    }
+
+    Ok(())
+}
+
+fn purge_trash(trash_purge_args: TrashPurge) -> Result<()> {
+    let files = if trash_purge_args.all {
+        items()?.into_iter().map(|item| item.id).collect()
+    } else {
+        trash_purge_args.files
+    };
+
+    for file in &files {
+        remove(file)?;
+    }
+
+    if dry_run::enabled() {
+        println!("Dry run: would remove {} files from the trash", files.len());
+    } else {
+        println!("Removed {} files from the trash", files.len());
+    }
+
+    Ok(())
+}
+
+/// Download files into the trash before they are deleted
+///
+/// Stops at the first failed download, so no file is deleted without a copy.
+///
+/// # Arguments
+///
+/// * `files` - Files to be deleted
+/// * `albums` - Known albums, the albums showing a file are recorded with it
+pub async fn save_files(files: &[FileId], albums: &[AlbumInfo]) -> Result<()> {
+    let options = DownloadOptions { overwrite: true, ..Default::default() };
+    let deleted = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    for file in files {
+        if dry_run::enabled() {
+            println!("Dry run: would move {file} to the trash");
+            continue;
+        }
+
+        let dir = item_dir(file)?.to_string_lossy().into_owned();
+        let downloaded = download::file_with(file_url(file), dir, &options).await?;
+
+        add(
+            &(Item {
+                id: file.clone(),
+                path: downloaded.path,
+                albums: albums
+                    .iter()
+                    .filter(|album| album.files.contains(file))
+                    .map(|album| album.short.clone())
+                    .collect(),
+                deleted,
+            })
+        )?;
+
+        println!("Moved {file} to the trash");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ args::{ CatboxArgs, CatboxCommand }, test };
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn trash() -> Result<()> {
+        let _state = test::isolate();
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "delete", "-u", "1", "--trash", "trash1.txt", "trash2.txt"]
+        );
+
+        if let CatboxCommand::Delete(delete_args) = args.command {
+            crate::delete_file(delete_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        let restored: FileId = "trash1.txt".parse()?;
+        let item = find(&restored)?.unwrap();
+        std::fs::write(&item.path, "restored")?;
+
+        for command in [
+            vec!["catbox", "trash", "list"],
+            vec!["catbox", "trash", "list", "--format", "json"],
+            vec!["catbox", "trash", "restore", "-u", "1", "trash1.txt"],
+        ] {
+            let args = CatboxArgs::parse_from(command);
+
+            if let CatboxCommand::Trash(trash_args) = args.command {
+                parse_trash(trash_args).await?;
+            } else {
+                panic!("Invalid subcommand");
+            }
+        }
+
+        assert!(find(&restored)?.is_none());
+
+        let args = CatboxArgs::parse_from(
+            vec!["catbox", "trash", "restore", "-u", "1", "trash1.txt"]
+        );
+
+        if let CatboxCommand::Trash(trash_args) = args.command {
+            assert!(parse_trash(trash_args).await.is_err());
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        assert!(CatboxArgs::try_parse_from(vec!["catbox", "trash", "purge"]).is_err());
+
+        let args = CatboxArgs::parse_from(vec!["catbox", "trash", "purge", "--all"]);
+
+        if let CatboxCommand::Trash(trash_args) = args.command {
+            parse_trash(trash_args).await?;
+        } else {
+            panic!("Invalid subcommand");
+        }
+
+        assert!(items()?.is_empty());
+
+        Ok(())
+    }
+}